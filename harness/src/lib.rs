@@ -0,0 +1,405 @@
+//! The common driver of the prover and verifier programs running inside the Gevulot NanoVM.
+//!
+//! A program parses its `Cli` from the task arguments and runs a function from the parsed
+//! arguments to its output files. The harness writes the log file returned with the outputs,
+//! catches errors and panics, times the phases and assembles the `TaskResult`.
+//!
+//! A panic hook writes a crash report (`<program>.crash.json` next to the log file) as soon as
+//! the panic happens, so it survives even when the process aborts afterwards.
+//!
+//! The programs declare their output files with `TaskContext::output` as they go, so the files
+//! written before a failure are returned too.
+//!
+//! Outside the NanoVM, `run` reads the task from the JSON file of `GEVULOT_TASK_FILE` and writes
+//! the result to `GEVULOT_RESULT_FILE`, which is how the mock node (tests/mock-node) runs them.
+use anyhow::Result;
+use clap::Parser;
+use gevulot_shim::{Task, TaskResult};
+use serde::{Deserialize, Serialize};
+
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::fmt::{Debug, Display};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard, Once};
+use std::time::Instant;
+
+pub const WORKSPACE: &str = "/workspace";
+pub const TASK_FILE_ENV: &str = "GEVULOT_TASK_FILE";
+pub const RESULT_FILE_ENV: &str = "GEVULOT_RESULT_FILE";
+
+pub type GeResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+/// The files produced by a program, the log file is appended by the harness.
+#[derive(Debug, Default)]
+pub struct Outputs {
+    pub files: Vec<String>,
+}
+
+impl Outputs {
+    pub fn new(files: Vec<String>) -> Self {
+        Outputs { files }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskStatus {
+    Succeeded,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PhaseTiming {
+    pub name: String,
+    pub duration_ms: u64,
+}
+
+/// Returned as the result data of the task.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TaskReport {
+    pub program: String,
+    pub status: TaskStatus,
+    pub error: Option<String>,
+    pub duration_ms: u64,
+    pub phases: Vec<PhaseTiming>,
+    pub files: Vec<String>,
+    pub missing: Vec<String>,
+    pub last_completed_phase: Option<String>,
+    pub crash_file: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InputHash {
+    pub path: String,
+    pub hash: String,
+}
+
+/// Written by the panic hook to `<program>.crash.json`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct CrashReport {
+    pub program: String,
+    pub message: String,
+    pub location: Option<String>,
+    pub thread: Option<String>,
+    pub current_phase: Option<String>,
+    pub last_completed_phase: Option<String>,
+    pub inputs: Vec<InputHash>,
+    pub backtrace: String,
+}
+
+// What the panic hook needs to know about the running task.
+#[derive(Default)]
+struct CrashState {
+    program: String,
+    log_path: String,
+    crash_path: String,
+    current_phase: Option<String>,
+    last_completed_phase: Option<String>,
+    inputs: Vec<InputHash>,
+}
+
+/// The task read from `GEVULOT_TASK_FILE`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LocalTask {
+    pub id: String,
+    pub args: Vec<String>,
+    pub files: Vec<String>,
+}
+
+/// Written to `GEVULOT_RESULT_FILE`, the result data and files of the `TaskResult`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LocalResult {
+    pub id: String,
+    pub report: TaskReport,
+    pub files: Vec<String>,
+}
+
+static CRASH_STATE: Mutex<Option<CrashState>> = Mutex::new(None);
+static LAST_REPORT: Mutex<Option<TaskReport>> = Mutex::new(None);
+static LAST_CRASH: Mutex<Option<CrashReport>> = Mutex::new(None);
+static PANIC_HOOK: Once = Once::new();
+
+fn lock<T>(m: &Mutex<T>) -> MutexGuard<'_, T> {
+    m.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn with_crash_state<F: FnOnce(&mut CrashState)>(f: F) {
+    if let Some(state) = lock(&CRASH_STATE).as_mut() {
+        f(state);
+    }
+}
+
+fn install_panic_hook() {
+    PANIC_HOOK.call_once(|| {
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let location = info
+                .location()
+                .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column()));
+            let mut report = CrashReport {
+                message: panic_message(info.payload()),
+                location,
+                thread: std::thread::current().name().map(str::to_string),
+                backtrace: Backtrace::force_capture().to_string(),
+                ..Default::default()
+            };
+            if let Some(state) = lock(&CRASH_STATE).as_ref() {
+                report.program = state.program.clone();
+                report.current_phase = state.current_phase.clone();
+                report.last_completed_phase = state.last_completed_phase.clone();
+                report.inputs = state.inputs.clone();
+                save_crash_report(state, &report);
+            }
+            *lock(&LAST_CRASH) = Some(report);
+            default_hook(info);
+        }));
+    });
+}
+
+fn save_crash_report(state: &CrashState, report: &CrashReport) {
+    match serde_json::to_string_pretty(report) {
+        Ok(content) => {
+            if let Err(e) = fs::write(&state.crash_path, content) {
+                log::error!("write crash report {} error: {}", state.crash_path, e);
+            }
+        }
+        Err(e) => log::error!("encode crash report error: {}", e),
+    }
+    if let Ok(mut log_file) = OpenOptions::new().append(true).open(&state.log_path) {
+        let _ = writeln!(
+            log_file,
+            "The {} panicked in phase {:?} at {:?}: {}, see {}",
+            report.program, report.current_phase, report.location, report.message, state.crash_path
+        );
+    }
+}
+
+pub struct TaskContext {
+    program: String,
+    log_path: String,
+    log_file: File,
+    phases: Vec<PhaseTiming>,
+    outputs: Vec<String>,
+}
+
+impl TaskContext {
+    fn new(program: &str, log_path: &str) -> Result<Self> {
+        File::create(log_path)?;
+        // appending, as the panic hook writes to the same file
+        let log_file = OpenOptions::new().append(true).open(log_path)?;
+        let crash_path = Path::new(log_path)
+            .with_file_name(format!("{}.crash.json", program))
+            .to_string_lossy()
+            .to_string();
+        let _ = fs::remove_file(&crash_path);
+        *lock(&CRASH_STATE) = Some(CrashState {
+            program: program.to_string(),
+            log_path: log_path.to_string(),
+            crash_path,
+            ..Default::default()
+        });
+        Ok(TaskContext {
+            program: program.to_string(),
+            log_path: log_path.to_string(),
+            log_file,
+            phases: vec![],
+            outputs: vec![],
+        })
+    }
+
+    pub fn program(&self) -> &str {
+        &self.program
+    }
+
+    pub fn log_path(&self) -> &str {
+        &self.log_path
+    }
+
+    /// Records the hash of an input file, reported in the log and in the crash report.
+    pub fn input(&mut self, path: &str) -> Result<String> {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update_mmap(path)?;
+        let hash = hasher.finalize().to_hex().to_string();
+        self.log(format!("input {}: {}", path, hash));
+        let input = InputHash {
+            path: path.to_string(),
+            hash: hash.clone(),
+        };
+        with_crash_state(|state| state.inputs.push(input));
+        Ok(hash)
+    }
+
+    /// Declares an output file, returned if it exists even when the program fails afterwards.
+    pub fn output<S: Into<String>>(&mut self, path: S) {
+        let path = path.into();
+        if !self.outputs.contains(&path) {
+            self.outputs.push(path);
+        }
+    }
+
+    /// Writes a line to the returned log file and to the program log.
+    pub fn log<T: Display>(&mut self, msg: T) {
+        log::info!("{}", msg);
+        if let Err(e) = writeln!(self.log_file, "{}", msg) {
+            log::warn!("write {} error: {}", self.log_path, e);
+        }
+    }
+
+    /// Runs one phase of the program and records how long it took.
+    pub fn phase<T, F>(&mut self, name: &str, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut Self) -> Result<T>,
+    {
+        self.log(format!("{} ...", name));
+        with_crash_state(|state| state.current_phase = Some(name.to_string()));
+        let start = Instant::now();
+        let result = f(self);
+        let duration = start.elapsed();
+        with_crash_state(|state| {
+            state.current_phase = None;
+            if result.is_ok() {
+                state.last_completed_phase = Some(name.to_string());
+            }
+        });
+        self.phases.push(PhaseTiming {
+            name: name.to_string(),
+            duration_ms: duration.as_millis() as u64,
+        });
+        match &result {
+            Ok(_) => self.log(format!("{} took: {:?}", name, duration)),
+            Err(e) => self.log(format!("{} failed after {:?}: {:#}", name, duration, e)),
+        }
+        result
+    }
+}
+
+/// Runs the task given by the Gevulot shim, or the local task of `GEVULOT_TASK_FILE` if it's set.
+pub fn run<F>(run_task: F) -> GeResult<()>
+where
+    F: Fn(Task) -> GeResult<TaskResult>,
+{
+    let Ok(task_file) = std::env::var(TASK_FILE_ENV) else {
+        return gevulot_shim::run(run_task);
+    };
+    let task: LocalTask = serde_json::from_str(&fs::read_to_string(&task_file)?)?;
+    let id = task.id.clone();
+    run_task(Task {
+        id: task.id,
+        args: task.args,
+        files: task.files,
+    })?;
+
+    let report = lock(&LAST_REPORT)
+        .take()
+        .ok_or("the task returned no report")?;
+    let result = LocalResult {
+        id,
+        files: report.files.clone(),
+        report,
+    };
+    let result_file =
+        std::env::var(RESULT_FILE_ENV).unwrap_or_else(|_| format!("{}.result", task_file));
+    fs::write(result_file, serde_json::to_string_pretty(&result)?)?;
+    Ok(())
+}
+
+/// Parses `C` from the task arguments, runs `body` and returns its outputs with the log file.
+pub fn execute<C, F>(task: &Task, program: &str, log_path: &str, body: F) -> GeResult<TaskResult>
+where
+    C: Parser + Debug,
+    F: FnOnce(C, &mut TaskContext) -> Result<Outputs>,
+{
+    let start = Instant::now();
+    let _ = env_logger::try_init();
+    install_panic_hook();
+    log::info!("0xEigenLabs {} : task.args: {:?}", program, &task.args);
+
+    let mut ctx = TaskContext::new(program, log_path)?;
+    let mut crash_file = None;
+    let result = match C::try_parse_from(&task.args) {
+        Ok(args) => {
+            ctx.log(format!("parameters: {:?}", args));
+            panic::catch_unwind(AssertUnwindSafe(|| body(args, &mut ctx))).unwrap_or_else(
+                |payload| {
+                    let message = match lock(&LAST_CRASH).take() {
+                        Some(crash) => format!(
+                            "panic in phase {:?} at {}: {}",
+                            crash.current_phase,
+                            crash.location.unwrap_or_default(),
+                            crash.message
+                        ),
+                        None => format!("panic: {}", panic_message(&*payload)),
+                    };
+                    with_crash_state(|state| crash_file = Some(state.crash_path.clone()));
+                    Err(anyhow::anyhow!(message))
+                },
+            )
+        }
+        Err(e) => Err(anyhow::anyhow!("invalid arguments: {}", e)),
+    };
+
+    // the declared outputs, written before a failure too
+    let (status, error) = match result {
+        Ok(outputs) => {
+            for file in outputs.files {
+                ctx.output(file);
+            }
+            (TaskStatus::Succeeded, None)
+        }
+        Err(e) => {
+            ctx.log(format!("The {} has error: {:#}", program, e));
+            (TaskStatus::Failed, Some(format!("{:#}", e)))
+        }
+    };
+    let mut files = std::mem::take(&mut ctx.outputs);
+    let mut last_completed_phase = None;
+    with_crash_state(|state| last_completed_phase = state.last_completed_phase.clone());
+    files.extend(crash_file.clone());
+    let duration = start.elapsed();
+    ctx.log(format!(
+        "the {} in Gevulot duration {:?}",
+        program, duration
+    ));
+
+    files.push(ctx.log_path.clone());
+    let (files, missing) = check_outputs(files);
+    let report = TaskReport {
+        program: program.to_string(),
+        status,
+        error,
+        duration_ms: duration.as_millis() as u64,
+        phases: ctx.phases,
+        files: files.clone(),
+        missing,
+        last_completed_phase,
+        crash_file,
+    };
+    let data = serde_json::to_vec(&report)?;
+    *lock(&LAST_REPORT) = Some(report);
+    task.result(data, files)
+}
+
+/// Splits the expected output files into the existing ones, which are returned to Gevulot,
+/// and the missing ones, which are reported in the result data instead.
+pub fn check_outputs(files: Vec<String>) -> (Vec<String>, Vec<String>) {
+    let (found, missing): (Vec<String>, Vec<String>) =
+        files.into_iter().partition(|f| Path::new(f).is_file());
+    for f in &missing {
+        log::warn!("The output file {} doesn't exist", f);
+    }
+    (found, missing)
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "unknown panic payload".to_string()
+    }
+}
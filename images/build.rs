@@ -0,0 +1,109 @@
+//! Embeds the powdr std library of `scripts/powdr` into the prover, see `src/powdr_std.rs`.
+//!
+//! The build fails if it differs from the std library of the powdr revision pinned in
+//! `Cargo.lock`. When the git checkout of that revision isn't found (e.g. vendored sources), the
+//! build only warns and the prover warns again when it starts.
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn main() {
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR"));
+    let std_dir = manifest_dir.join("scripts").join("powdr");
+    println!("cargo:rerun-if-changed={}", std_dir.display());
+    println!("cargo:rerun-if-changed=../Cargo.lock");
+
+    let files = asm_files(&std_dir);
+    let hash = tree_hash(&std_dir, &files);
+    let expected = powdr_checkout_std(&manifest_dir).map(|dir| tree_hash(&dir, &asm_files(&dir)));
+    match &expected {
+        Some(expected) if *expected != hash => panic!(
+            "scripts/powdr ({}) differs from the std library of the pinned powdr ({}), copy the \
+             std directory of the powdr revision in Cargo.lock to scripts/powdr",
+            hash, expected
+        ),
+        Some(_) => {}
+        None => println!(
+            "cargo:warning=the git checkout of the pinned powdr isn't found, scripts/powdr isn't \
+             checked; fetch it with `cargo fetch` to check it"
+        ),
+    }
+
+    let mut out = String::new();
+    out.push_str(&format!("pub const POWDR_STD_HASH: &str = {:?};\n", hash));
+    out.push_str(&format!(
+        "pub const POWDR_STD_EXPECTED_HASH: Option<&str> = {:?};\n",
+        expected
+    ));
+    out.push_str("pub static POWDR_STD_FILES: &[(&str, &[u8])] = &[\n");
+    for file in &files {
+        out.push_str(&format!(
+            "    ({:?}, include_bytes!({:?})),\n",
+            relative(&std_dir, file),
+            file.display().to_string()
+        ));
+    }
+    out.push_str("];\n");
+    let out_dir = PathBuf::from(env::var("OUT_DIR").expect("OUT_DIR"));
+    fs::write(out_dir.join("powdr_std.rs"), out).expect("write powdr_std.rs");
+}
+
+fn asm_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = vec![];
+    for entry in fs::read_dir(dir).unwrap_or_else(|e| panic!("read {:?}: {}", dir, e)) {
+        let path = entry.expect("read_dir entry").path();
+        if path.is_dir() {
+            files.extend(asm_files(&path));
+        } else if path.extension().is_some_and(|e| e == "asm") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    files
+}
+
+fn relative(dir: &Path, file: &Path) -> String {
+    let relative = file.strip_prefix(dir).expect("a file of the std directory");
+    let parts: Vec<_> = relative.iter().map(|p| p.to_string_lossy()).collect();
+    parts.join("/")
+}
+
+// Must be the same as `powdr_std::tree_hash`.
+fn tree_hash(dir: &Path, files: &[PathBuf]) -> String {
+    let mut entries: Vec<(String, Vec<u8>)> = files
+        .iter()
+        .map(|f| (relative(dir, f), fs::read(f).expect("read a std file")))
+        .collect();
+    entries.sort();
+    let mut hasher = blake3::Hasher::new();
+    for (name, content) in &entries {
+        hasher.update(name.as_bytes());
+        hasher.update(&[0]);
+        hasher.update(&(content.len() as u64).to_le_bytes());
+        hasher.update(content);
+    }
+    hasher.finalize().to_hex().to_string()
+}
+
+// The `std` directory of the git checkout of the powdr revision in `Cargo.lock`, cargo keeps it
+// in `$CARGO_HOME/git/checkouts/powdr-<hash>/<short revision>`.
+fn powdr_checkout_std(manifest_dir: &Path) -> Option<PathBuf> {
+    let lock = fs::read_to_string(manifest_dir.parent()?.join("Cargo.lock")).ok()?;
+    let mut lines = lock.lines();
+    let rev = loop {
+        if lines.next()? == "name = \"powdr\"" {
+            let source = lines.find(|l| l.starts_with("source = "))?;
+            break source.rsplit_once('#')?.1.trim_end_matches('"').to_string();
+        }
+    };
+    let cargo_home = env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cargo")))?;
+    let checkouts = cargo_home.join("git").join("checkouts");
+    fs::read_dir(checkouts)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("powdr-"))
+        .map(|entry| entry.path().join(&rev[..rev.len().min(7)]).join("std"))
+        .find(|std| std.join("mod.asm").is_file())
+}
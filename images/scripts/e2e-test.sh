@@ -1 +1,2 @@
-RUST_LOG=debug ../target/release/eigen-gevulot-e2e-tests  --local-http-url http://4.145.88.10:8080   --json-rpc-url  http://api.devnet.gevulot.com:9944  --trace_file reth.block.json --task_name evm --bi_file evm_chunks_0.data --asm_file evm.asm --chunk_id 0  --key-file localkey.pki --prover-hash $1  --verifier-hash $2
\ No newline at end of file
+# the node, the key and the http file server are the devnet profile of eigen-gevulot.toml
+RUST_LOG=debug ../target/release/eigen-gevulot-e2e-tests  --profile devnet  --trace_file reth.block.json --task_name evm --bi_file evm_chunks_0.data --asm_file evm.asm --chunk_id 0  --prover_hash $1  --verifier_hash $2
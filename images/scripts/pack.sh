@@ -1,44 +1,32 @@
+# jq reads the program hashes from the output of the deployment
+command -v jq >/dev/null || { echo "pack.sh needs jq, e.g. apt install jq"; exit 1; }
+
 if [ "$1" == "debug" ];then
     echo "debug version:"
-    cp  ../target/debug/gevulot-prover .
-    cp  ../target/debug/gevulot-verifier .
+    TARGET=../target/debug
 else
     echo "release version:"
-    cp  ../target/release/gevulot-prover .
-    cp  ../target/release/gevulot-verifier .
+    TARGET=../target/release
 fi
+cp  $TARGET/gevulot-prover .
+cp  $TARGET/gevulot-verifier .
+# the powdr std library is embedded into gevulot-prover, no std file is packaged
+$TARGET/eigen-gevulot manifest --program gevulot-prover --output my_prover.json || exit 1
+$TARGET/eigen-gevulot manifest --program gevulot-verifier --rust_log debug --output my_verifier.json || exit 1
 ops build ./gevulot-prover  -c my_prover.json
 ops build ./gevulot-verifier  -c my_verifier.json
 
-cp   ~/.ops/images/gevulot-prover  /data/http/
-cp   ~/.ops/images/gevulot-verifier  /data/http/
-
-
-PHSH=$(gevulot-cli calculate-hash --file  ~/.ops/images/gevulot-prover)
-echo "prover hash:$PHSH"
-
-p_array=(${PHSH//:/ })
-p_hsh=${p_array[6]}
-
-VHSH=$(gevulot-cli calculate-hash --file  ~/.ops/images/gevulot-verifier)
-echo "verifier hash: $VHSH "
-
-v_array=(${VHSH//:/ })
-v_hsh=${v_array[6]}
-
 echo " "
-
 echo "deploy the new image to gevulot platform...)"
-TEMPLOG=$(./deploy.sh $p_hsh $v_hsh )
-#echo "dd:$TEMPLOG"
-
-p_array=(${TEMPLOG//:/ })
+# publishes the images to /data/http/, computes their hashes and deploys them, see deploy.json
+$TARGET/eigen-gevulot deploy --profile devnet --key_file my-local-key.pki \
+    --deploy_config deploy.json --output deployed.json || exit 1
 
-tphsh=${p_array[12]}
-tvhsh=${p_array[15]}
+tphsh=$(jq -r .prover_hash deployed.json)
+tvhsh=$(jq -r .verifier_hash deployed.json)
 echo "task P hash:$tphsh"
 echo "task V hash:$tvhsh"
 ##
 echo " "
 echo " run evm task : "
- ./e2e-test.sh  $tphsh ${tvhsh%.}
+ ./e2e-test.sh  $tphsh $tvhsh
@@ -0,0 +1,247 @@
+//! The node operations used by the client, implemented by the Gevulot `RpcClient` and by an
+//! in-memory `MemoryBackend` which serves scripted transactions and trees.
+use gevulot_node::rpc_client::RpcClient;
+use gevulot_node::types::transaction::Created;
+use gevulot_node::types::{Hash, Transaction, TransactionTree};
+use serde_json::Value;
+
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::payload::TxOutput;
+
+type BoxResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+// The futures are awaited in place by the client, no Send bound is needed.
+#[allow(async_fn_in_trait)]
+pub trait ProvingBackend {
+    async fn send_transaction(&self, tx: &Transaction<Created>) -> BoxResult<()>;
+
+    async fn get_transaction(&self, hash: &Hash) -> BoxResult<TxOutput>;
+
+    async fn get_tx_tree(&self, hash: &Hash) -> BoxResult<TransactionTree>;
+}
+
+impl ProvingBackend for RpcClient {
+    async fn send_transaction(&self, tx: &Transaction<Created>) -> BoxResult<()> {
+        RpcClient::send_transaction(self, tx).await?;
+        Ok(())
+    }
+
+    async fn get_transaction(&self, hash: &Hash) -> BoxResult<TxOutput> {
+        let tx_output = RpcClient::get_transaction(self, hash).await?;
+        Ok(TxOutput::decode(&hash.to_string(), &tx_output)?)
+    }
+
+    async fn get_tx_tree(&self, hash: &Hash) -> BoxResult<TransactionTree> {
+        Ok(RpcClient::get_tx_tree(self, hash).await?)
+    }
+}
+
+/// A node in memory. The transactions and trees are stored in their JSON form, so malformed
+/// payloads can be served too. A missing tree is reported as not found.
+#[derive(Debug, Default)]
+pub struct MemoryBackend {
+    state: Mutex<MemoryState>,
+}
+
+#[derive(Debug, Default)]
+struct MemoryState {
+    txs: HashMap<String, Value>,
+    trees: HashMap<String, Value>,
+    sent: Vec<String>,
+    failures: u32,
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        MemoryBackend::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MemoryState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Stores the JSON form of a `TransactionOutput`.
+    pub fn insert_tx(&self, hash: &str, tx: Value) {
+        self.lock().txs.insert(hash.to_string(), tx);
+    }
+
+    /// Stores the JSON form of a `TransactionTree`.
+    pub fn set_tree(&self, hash: &str, tree: Value) {
+        self.lock().trees.insert(hash.to_string(), tree);
+    }
+
+    pub fn remove_tree(&self, hash: &str) {
+        self.lock().trees.remove(hash);
+    }
+
+    /// Makes the next `count` calls fail, as an unreachable node.
+    pub fn fail_next(&self, count: u32) {
+        self.lock().failures = count;
+    }
+
+    /// The hashes of the sent transactions, in order.
+    pub fn sent(&self) -> Vec<String> {
+        self.lock().sent.clone()
+    }
+
+    fn check_failure(state: &mut MemoryState) -> BoxResult<()> {
+        if state.failures > 0 {
+            state.failures -= 1;
+            return Err("the node is unavailable".into());
+        }
+        Ok(())
+    }
+}
+
+impl ProvingBackend for MemoryBackend {
+    async fn send_transaction(&self, tx: &Transaction<Created>) -> BoxResult<()> {
+        let mut state = self.lock();
+        Self::check_failure(&mut state)?;
+        let hash = tx.hash.to_string();
+        let output = TxOutput::from_sent(&serde_json::to_value(tx)?)?;
+        state
+            .txs
+            .insert(hash.clone(), serde_json::to_value(output)?);
+        state.sent.push(hash);
+        Ok(())
+    }
+
+    async fn get_transaction(&self, hash: &Hash) -> BoxResult<TxOutput> {
+        let mut state = self.lock();
+        Self::check_failure(&mut state)?;
+        let hash = hash.to_string();
+        let tx = state
+            .txs
+            .get(&hash)
+            .ok_or_else(|| format!("the transaction {} isn't found", hash))?;
+        Ok(TxOutput::decode(&hash, tx)?)
+    }
+
+    async fn get_tx_tree(&self, hash: &Hash) -> BoxResult<TransactionTree> {
+        let mut state = self.lock();
+        Self::check_failure(&mut state)?;
+        let hash = hash.to_string();
+        let tree = state
+            .trees
+            .get(&hash)
+            .ok_or_else(|| format!("the tree of {} isn't found", hash))?;
+        Ok(serde_json::from_value(tree.clone())?)
+    }
+}
+
+/// Builders of transactions and trees for the tests of the client paths.
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use super::*;
+    use crate::payload::{ProofPayload, TxFile, TxPayload, VerificationPayload};
+    use crate::poll::PollPolicy;
+    use std::time::Duration;
+
+    pub fn hash(byte: u8) -> Hash {
+        (&[byte; 32][..]).into()
+    }
+
+    /// Polls every 10s for 2 minutes, without jitter.
+    pub fn policy() -> PollPolicy {
+        PollPolicy {
+            initial_delay: Duration::from_secs(1),
+            interval: Duration::from_secs(10),
+            max_interval: Duration::from_secs(10),
+            multiplier: 1.0,
+            jitter: 0.0,
+            deadline: Duration::from_secs(120),
+        }
+    }
+
+    pub fn insert(backend: &MemoryBackend, hash: &Hash, payload: TxPayload) {
+        let tx = TxOutput {
+            author: String::new(),
+            hash: hash.to_string(),
+            payload,
+            nonce: 0,
+            signature: String::new(),
+        };
+        backend.insert_tx(&hash.to_string(), serde_json::to_value(tx).unwrap());
+    }
+
+    pub fn proof(parent: &Hash) -> TxPayload {
+        TxPayload::Proof(ProofPayload {
+            parent: parent.to_string(),
+            prover: hash(0xa0).to_string(),
+            proof: String::new(),
+            files: vec![],
+        })
+    }
+
+    /// A verification of the verifier `hash(0xb0)`.
+    pub fn verification(parent: &Hash) -> TxPayload {
+        verification_by(parent, &hash(0xb0))
+    }
+
+    pub fn verification_by(parent: &Hash, verifier: &Hash) -> TxPayload {
+        TxPayload::Verification(verification_payload(parent, verifier, &hash(0xc0)))
+    }
+
+    /// A verification returning one file, `lr_proof.bin` with the given checksum.
+    pub fn verification_payload(
+        parent: &Hash,
+        verifier: &Hash,
+        checksum: &Hash,
+    ) -> VerificationPayload {
+        VerificationPayload {
+            parent: parent.to_string(),
+            verifier: verifier.to_string(),
+            verification: String::new(),
+            files: vec![TxFile {
+                url: "http://127.0.0.1:9995/txfiles/lr_proof.bin".to_string(),
+                checksum: checksum.to_string(),
+                vm_path: "/workspace/lr_proof.bin".to_string(),
+            }],
+        }
+    }
+
+    /// The Run transaction `root` with the given leaves.
+    pub fn set_tree(backend: &MemoryBackend, root: &Hash, leaves: &[Hash]) {
+        let tree = TransactionTree::Root {
+            hash: *root,
+            children: leaves
+                .iter()
+                .map(|leaf| TransactionTree::Leaf { hash: *leaf })
+                .collect(),
+        };
+        backend.set_tree(&root.to_string(), serde_json::to_value(&tree).unwrap());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixtures::*;
+    use super::*;
+
+    #[tokio::test]
+    async fn missing_tree_is_an_error() {
+        let backend = MemoryBackend::new();
+        let err = backend.get_tx_tree(&hash(1)).await.unwrap_err();
+        assert!(err.to_string().contains("isn't found"), "{}", err);
+
+        set_tree(&backend, &hash(1), &[hash(2)]);
+        assert!(backend.get_tx_tree(&hash(1)).await.is_ok());
+        backend.remove_tree(&hash(1).to_string());
+        assert!(backend.get_tx_tree(&hash(1)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn fail_next_fails_that_many_calls() {
+        let backend = MemoryBackend::new();
+        set_tree(&backend, &hash(1), &[hash(2)]);
+        insert(&backend, &hash(2), proof(&hash(1)));
+        backend.fail_next(2);
+
+        assert!(backend.get_tx_tree(&hash(1)).await.is_err());
+        assert!(backend.get_transaction(&hash(2)).await.is_err());
+        assert!(backend.get_tx_tree(&hash(1)).await.is_ok());
+        assert!(backend.get_transaction(&hash(2)).await.is_ok());
+    }
+}
@@ -0,0 +1,250 @@
+//! Proves all the chunks of an execution, several at a time, and collects their results in
+//! chunk order.
+use anyhow::{bail, Context, Result};
+use futures_util::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::backend::ProvingBackend;
+use crate::client::{ProverClient, ProvingRequest};
+
+/// The bootloader input file of one chunk, `<task>_chunks_<i>.data`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkInput {
+    pub chunk_id: usize,
+    pub bi_file: PathBuf,
+}
+
+/// Lists the chunk files of the task in `dir`, ordered by chunk id.
+pub fn find_chunks(dir: &Path, task_name: &str) -> Result<Vec<ChunkInput>> {
+    let prefix = format!("{}_chunks_", task_name);
+    let mut chunks = vec![];
+    for entry in fs::read_dir(dir).with_context(|| format!("read {:?}", dir))? {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        let chunk_id = name
+            .strip_prefix(&prefix)
+            .and_then(|n| n.strip_suffix(".data"))
+            .and_then(|n| n.parse::<usize>().ok());
+        if let Some(chunk_id) = chunk_id {
+            chunks.push(ChunkInput {
+                chunk_id,
+                bi_file: path,
+            });
+        }
+    }
+    if chunks.is_empty() {
+        bail!("no {}<i>.data file in {:?}", prefix, dir);
+    }
+    chunks.sort_by_key(|c| c.chunk_id);
+    Ok(chunks)
+}
+
+#[derive(Debug, Clone)]
+pub struct BatchOptions {
+    /// The number of chunks proved at the same time.
+    pub concurrency: usize,
+    /// The results of chunk `i` are downloaded to `<out_dir>/chunk_<i>`.
+    pub out_dir: PathBuf,
+    /// How many times the failed chunks are submitted again.
+    pub retries: u32,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        BatchOptions {
+            concurrency: 4,
+            out_dir: PathBuf::from("/tmp/gevulot"),
+            retries: 0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ChunkOutcome {
+    pub chunk_id: usize,
+    pub bi_file: PathBuf,
+    pub attempts: u32,
+    /// The Run transaction of the last attempt.
+    pub tx_hash: Option<String>,
+    pub files: Vec<PathBuf>,
+    pub error: Option<String>,
+}
+
+impl ChunkOutcome {
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Saved as `<out_dir>/summary.json`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct BatchSummary {
+    pub task_name: String,
+    pub chunks: Vec<ChunkOutcome>,
+}
+
+impl BatchSummary {
+    pub fn path(out_dir: &Path) -> PathBuf {
+        out_dir.join("summary.json")
+    }
+
+    pub fn save(&self, out_dir: &Path) -> Result<()> {
+        let path = Self::path(out_dir);
+        fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("write {:?}", path))
+    }
+
+    pub fn load(out_dir: &Path) -> Result<Self> {
+        let path = Self::path(out_dir);
+        let content = fs::read_to_string(&path).with_context(|| format!("read {:?}", path))?;
+        serde_json::from_str(&content).with_context(|| format!("decode {:?}", path))
+    }
+
+    pub fn succeeded(&self) -> impl Iterator<Item = &ChunkOutcome> {
+        self.chunks.iter().filter(|c| c.succeeded())
+    }
+
+    pub fn failed(&self) -> impl Iterator<Item = &ChunkOutcome> {
+        self.chunks.iter().filter(|c| !c.succeeded())
+    }
+
+    /// The inputs of the failed chunks, to submit them again.
+    pub fn failed_chunks(&self) -> Vec<ChunkInput> {
+        self.failed()
+            .map(|c| ChunkInput {
+                chunk_id: c.chunk_id,
+                bi_file: c.bi_file.clone(),
+            })
+            .collect()
+    }
+
+    /// Replaces the outcomes of the same chunks, keeping the chunk order.
+    pub fn merge(&mut self, outcomes: Vec<ChunkOutcome>) {
+        for outcome in outcomes {
+            match self
+                .chunks
+                .iter_mut()
+                .find(|c| c.chunk_id == outcome.chunk_id)
+            {
+                Some(c) => *c = outcome,
+                None => self.chunks.push(outcome),
+            }
+        }
+        self.chunks.sort_by_key(|c| c.chunk_id);
+    }
+
+    pub fn print(&self) {
+        for c in &self.chunks {
+            match &c.error {
+                None => println!(
+                    "chunk {}: ok, {} files, tx {}",
+                    c.chunk_id,
+                    c.files.len(),
+                    c.tx_hash.as_deref().unwrap_or("-")
+                ),
+                Some(e) => println!(
+                    "chunk {}: failed after {} attempts, tx {}: {}",
+                    c.chunk_id,
+                    c.attempts,
+                    c.tx_hash.as_deref().unwrap_or("-"),
+                    e
+                ),
+            }
+        }
+        println!(
+            "{} succeeded, {} failed",
+            self.succeeded().count(),
+            self.failed().count()
+        );
+    }
+}
+
+/// Submits one workflow per chunk, with the other fields of `template`, and downloads the
+/// results. The failed chunks are submitted again up to `options.retries` times.
+///
+/// The outcomes are merged into `previous`, e.g. the summary of an earlier run whose failed
+/// chunks are submitted again, and the merged summary is saved after every round, so the
+/// earlier outcomes survive a crash.
+pub async fn prove_chunks<B: ProvingBackend>(
+    client: &ProverClient<B>,
+    template: &ProvingRequest,
+    chunks: Vec<ChunkInput>,
+    previous: Option<BatchSummary>,
+    options: &BatchOptions,
+) -> Result<BatchSummary> {
+    fs::create_dir_all(&options.out_dir)
+        .with_context(|| format!("create {:?}", options.out_dir))?;
+    let mut summary = previous.unwrap_or_else(|| BatchSummary {
+        task_name: template.task_name.clone(),
+        chunks: vec![],
+    });
+
+    let mut pending: Vec<(ChunkInput, u32)> = chunks.into_iter().map(|c| (c, 0)).collect();
+    for round in 0..=options.retries {
+        if pending.is_empty() {
+            break;
+        }
+        if round > 0 {
+            log::info!("Resubmit {} failed chunks", pending.len());
+        }
+        let outcomes: Vec<ChunkOutcome> = stream::iter(pending)
+            .map(|(chunk, attempts)| prove_chunk(client, template, chunk, attempts + 1, options))
+            .buffer_unordered(options.concurrency.max(1))
+            .collect()
+            .await;
+        pending = outcomes
+            .iter()
+            .filter(|c| !c.succeeded())
+            .map(|c| {
+                let chunk = ChunkInput {
+                    chunk_id: c.chunk_id,
+                    bi_file: c.bi_file.clone(),
+                };
+                (chunk, c.attempts)
+            })
+            .collect();
+        summary.merge(outcomes);
+        summary.save(&options.out_dir)?;
+    }
+    Ok(summary)
+}
+
+async fn prove_chunk<B: ProvingBackend>(
+    client: &ProverClient<B>,
+    template: &ProvingRequest,
+    chunk: ChunkInput,
+    attempts: u32,
+    options: &BatchOptions,
+) -> ChunkOutcome {
+    let mut outcome = ChunkOutcome {
+        chunk_id: chunk.chunk_id,
+        bi_file: chunk.bi_file.clone(),
+        attempts,
+        tx_hash: None,
+        files: vec![],
+        error: None,
+    };
+    let out_dir = options.out_dir.join(format!("chunk_{}", chunk.chunk_id));
+    let result = async {
+        let mut request = template.clone();
+        request.bi_file = chunk.bi_file.clone();
+        request.chunk_id = chunk.chunk_id;
+        let job = client.submit(request).await?;
+        outcome.tx_hash = Some(job.tx_hash().to_string());
+        job.fetch_results(&out_dir).await
+    }
+    .await;
+    match result {
+        Ok(files) => outcome.files = files,
+        Err(e) => {
+            log::error!("chunk {} failed: {:#}", chunk.chunk_id, e);
+            outcome.error = Some(format!("{:#}", e));
+        }
+    }
+    outcome
+}
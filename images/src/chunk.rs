@@ -0,0 +1,121 @@
+//! Splits a task into the bootloader inputs of its continuation chunks, `<task>_chunks_<i>.data`,
+//! which are proved one by one on Gevulot.
+use anyhow::{anyhow, ensure, Context, Result};
+use powdr::number::{FieldElement, GoldilocksField};
+use powdr::riscv::continuations::rust_continuations_dry_run;
+use powdr::riscv::{compile_rust, Runtime};
+use powdr::Pipeline;
+
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+// The channel of the trace file, as read by the guest programs.
+const TEST_CHANNEL: u32 = 1;
+
+/// Compiles the guest program of `workspace`, runs it on the trace and returns the bootloader
+/// inputs of every chunk with its start of the shutdown routine.
+pub fn zkvm_generate_chunks(
+    workspace: &str,
+    suite_json: &String,
+    output_path: &str,
+) -> Result<Vec<(Vec<GoldilocksField>, u64)>> {
+    log::info!("Compiling Rust...");
+    let force_overwrite = true;
+    let with_bootloader = true;
+    let (asm_file_path, asm_contents) = compile_rust::<GoldilocksField>(
+        workspace,
+        Path::new(output_path),
+        force_overwrite,
+        &Runtime::base().with_poseidon(),
+        with_bootloader,
+    )
+    .ok_or_else(|| anyhow!("could not compile rust"))?;
+
+    let mut pipeline = Pipeline::<GoldilocksField>::default()
+        .with_output(output_path.into(), true)
+        .from_asm_string(asm_contents, Some(asm_file_path))
+        .with_prover_inputs(Default::default())
+        .add_data(TEST_CHANNEL, suite_json);
+
+    log::info!("Running powdr-riscv executor in trace mode for continuations...");
+    let start = Instant::now();
+
+    let bootloader_inputs = rust_continuations_dry_run(&mut pipeline);
+
+    let duration = start.elapsed();
+    log::info!(
+        "Trace executor took: {:?}, input size: {:?}",
+        duration,
+        bootloader_inputs.len()
+    );
+
+    Ok(bootloader_inputs)
+}
+
+/// Generates the chunks of the task and writes them to `<out_dir>/<task>_chunks_<i>.data`, with
+/// the powdr outputs in `out_dir` too.
+pub fn generate_chunk_files(
+    workspace: &str,
+    trace_file: &Path,
+    task_name: &str,
+    out_dir: &Path,
+) -> Result<Vec<PathBuf>> {
+    let suite_json =
+        fs::read_to_string(trace_file).with_context(|| format!("read {:?}", trace_file))?;
+    fs::create_dir_all(out_dir).with_context(|| format!("create {:?}", out_dir))?;
+    let output_path = out_dir
+        .to_str()
+        .ok_or_else(|| anyhow!("the path {:?} isn't valid UTF-8", out_dir))?;
+    let chunks = zkvm_generate_chunks(workspace, &suite_json, output_path)?;
+
+    let mut files = vec![];
+    for (i, (bi, start_of_shutdown_routine)) in chunks.iter().enumerate() {
+        let file = out_dir.join(format!("{}_chunks_{}.data", task_name, i));
+        write_bootloader_inputs(&file, *start_of_shutdown_routine, bi)?;
+        files.push(file);
+    }
+    Ok(files)
+}
+
+// The file starts with the start_of_shutdown_routine followed by the bootloader inputs, all u64 LE.
+pub fn read_bootloader_inputs(bi_file: &str) -> Result<(u64, Vec<GoldilocksField>)> {
+    let mut f = fs::File::open(bi_file)?;
+    let metadata = fs::metadata(bi_file)?;
+    let file_size = metadata.len() as usize;
+
+    ensure!(
+        file_size % 8 == 0 && file_size >= 8,
+        "invalid bootloader input file size {}",
+        file_size
+    );
+    // read the start_of_shutdown_routine
+    let mut buffer = [0u8; 8];
+    f.read_exact(&mut buffer)?;
+    let start_of_shutdown_routine: u64 = u64::from_le_bytes(buffer);
+
+    let file_size = file_size - 8;
+    let mut buffer = vec![0; file_size];
+    f.read_exact(&mut buffer)?;
+    let mut bi = vec![GoldilocksField::default(); file_size / 8];
+    bi.iter_mut().zip(buffer.chunks(8)).for_each(|(out, bin)| {
+        *out = GoldilocksField::from_bytes_le(bin);
+    });
+    Ok((start_of_shutdown_routine, bi))
+}
+
+pub fn write_bootloader_inputs(
+    bi_file: &Path,
+    start_of_shutdown_routine: u64,
+    bi: &[GoldilocksField],
+) -> Result<()> {
+    let mut buffer = Vec::with_capacity((bi.len() + 1) * 8);
+    buffer.extend_from_slice(&start_of_shutdown_routine.to_le_bytes());
+    for e in bi {
+        let mut bytes = e.to_bytes_le();
+        bytes.resize(8, 0);
+        buffer.extend_from_slice(&bytes);
+    }
+    fs::write(bi_file, buffer).with_context(|| format!("write {:?}", bi_file))
+}
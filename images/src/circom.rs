@@ -0,0 +1,93 @@
+//! Options of the circom verifier generated for a chunk proof.
+use anyhow::{Context, Result};
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use starky::pil2circom::StarkOption;
+
+use std::fs;
+use std::path::Path;
+
+/// Mirrors `pil2circom::StarkOption`, the defaults produce a chunk verifier without `main`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+// a misspelled option of the config file is an error rather than its default
+#[serde(default, deny_unknown_fields)]
+pub struct CircomOptions {
+    pub enable_input: bool,
+    pub verkey_input: bool,
+    pub skip_main: bool,
+    pub agg_stage: bool,
+}
+
+impl Default for CircomOptions {
+    fn default() -> Self {
+        CircomOptions {
+            enable_input: false,
+            verkey_input: false,
+            skip_main: true,
+            agg_stage: false,
+        }
+    }
+}
+
+impl CircomOptions {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path).with_context(|| format!("read {:?}", path))?;
+        serde_json::from_str(&content).with_context(|| format!("decode circom options {:?}", path))
+    }
+
+    /// Applies the options given on the command line on top of the config file.
+    pub fn with_overrides(mut self, overrides: CircomOverrides) -> Self {
+        let CircomOverrides {
+            enable_input,
+            verkey_input,
+            skip_main,
+            agg_stage,
+        } = overrides;
+        self.enable_input = enable_input.unwrap_or(self.enable_input);
+        self.verkey_input = verkey_input.unwrap_or(self.verkey_input);
+        self.skip_main = skip_main.unwrap_or(self.skip_main);
+        self.agg_stage = agg_stage.unwrap_or(self.agg_stage);
+        self
+    }
+
+    pub fn to_stark_option(&self) -> StarkOption {
+        StarkOption {
+            enable_input: self.enable_input,
+            verkey_input: self.verkey_input,
+            skip_main: self.skip_main,
+            agg_stage: self.agg_stage,
+        }
+    }
+}
+
+/// The options of a proving request overriding the circom config of the prover, passed as its
+/// `--enable_input`, `--verkey_input`, `--skip_main` and `--agg_stage` arguments.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, Args)]
+#[serde(default)]
+pub struct CircomOverrides {
+    #[arg(long = "enable_input", action = clap::ArgAction::Set)]
+    pub enable_input: Option<bool>,
+    #[arg(long = "verkey_input", action = clap::ArgAction::Set)]
+    pub verkey_input: Option<bool>,
+    #[arg(long = "skip_main", action = clap::ArgAction::Set)]
+    pub skip_main: Option<bool>,
+    #[arg(long = "agg_stage", action = clap::ArgAction::Set)]
+    pub agg_stage: Option<bool>,
+}
+
+impl CircomOverrides {
+    /// The prover arguments of the given options, e.g. `--agg_stage true`.
+    pub fn args(&self) -> Vec<String> {
+        [
+            ("--enable_input", self.enable_input),
+            ("--verkey_input", self.verkey_input),
+            ("--skip_main", self.skip_main),
+            ("--agg_stage", self.agg_stage),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| value.map(|v| [name.to_string(), v.to_string()]))
+        .flatten()
+        .collect()
+    }
+}
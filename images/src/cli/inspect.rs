@@ -0,0 +1,89 @@
+//! `inspect` and `verify`: the starky proof returned by the Gevulot prover.
+use anyhow::{bail, Result};
+use clap::Subcommand;
+use images::proof::{
+    check_results, diff_proofs, load_proof, load_stark_struct, print_summary, summarize, write_zkin,
+};
+
+use std::path::Path;
+
+#[derive(Debug, Subcommand)]
+pub enum InspectCommand {
+    /// Print the roots, public inputs, FRI layers and size breakdown of a proof.
+    Summary {
+        proof_file: String,
+        /// The StarkStruct used by the prover, printed alongside the proof.
+        #[arg(long = "stark_struct")]
+        stark_struct: Option<String>,
+    },
+    /// List the fields which differ between two proofs.
+    Diff { left: String, right: String },
+    /// Convert a proof to the zkin.json input of the circom verifier.
+    Zkin {
+        proof_file: String,
+        zkin_file: String,
+    },
+}
+
+pub fn inspect(command: InspectCommand, json: bool) -> Result<()> {
+    match command {
+        InspectCommand::Summary {
+            proof_file,
+            stark_struct,
+        } => {
+            let proof = load_proof(&proof_file)?;
+            let stark_struct = stark_struct.map(load_stark_struct).transpose()?;
+            let summary = summarize(&proof, stark_struct);
+            if json {
+                println!("{}", serde_json::to_string_pretty(&summary)?);
+            } else {
+                print_summary(&summary);
+            }
+        }
+        InspectCommand::Diff { left, right } => {
+            let left = load_proof(&left)?;
+            let right = load_proof(&right)?;
+            let diffs = diff_proofs(&left, &right);
+            if json {
+                println!("{}", serde_json::to_string_pretty(&diffs)?);
+            } else if diffs.is_empty() {
+                println!("proofs are identical");
+            } else {
+                for d in &diffs {
+                    println!(
+                        "{}: {} != {}",
+                        d.key,
+                        d.left.as_deref().unwrap_or("<missing>"),
+                        d.right.as_deref().unwrap_or("<missing>")
+                    );
+                }
+            }
+        }
+        InspectCommand::Zkin {
+            proof_file,
+            zkin_file,
+        } => {
+            let proof = load_proof(&proof_file)?;
+            write_zkin(&proof, &zkin_file)?;
+            log::info!("write {} to {}", proof_file, zkin_file);
+        }
+    }
+    Ok(())
+}
+
+/// Checks the results of a chunk downloaded by `fetch` or `submit --wait`.
+pub fn verify(dir: &Path, task_name: &str, chunk_id: usize, json: bool) -> Result<()> {
+    let check = check_results(dir, task_name, chunk_id)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&check)?);
+    } else {
+        println!("{}: checked {}", check.manifest, check.checked.join(", "));
+        for problem in &check.problems {
+            println!("  {}", problem);
+        }
+    }
+    if !check.is_ok() {
+        bail!("the results of chunk {} in {:?} are invalid", chunk_id, dir);
+    }
+    Ok(())
+}
@@ -0,0 +1,150 @@
+//! `jobs` and `fetch`: the proving jobs recorded in the job store.
+use anyhow::{bail, Result};
+use clap::Subcommand;
+use futures_util::stream::{self, StreamExt};
+use images::client::ProverClient;
+use images::config::ClientConfig;
+use images::jobs::{JobRecord, JobStore};
+use images::poll::PollPolicy;
+use serde_json::json;
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[derive(Debug, Subcommand)]
+pub enum JobsCommand {
+    /// Print the recorded jobs, the oldest first.
+    List,
+    /// Wait for and download the results of every unfinished job.
+    Resume {
+        //the results are saved to the output directory of the submission by default.
+        #[arg(long = "to")]
+        to: Option<PathBuf>,
+        #[arg(long = "concurrency", default_value_t = 4)]
+        concurrency: usize,
+    },
+    /// Remove the downloaded jobs older than `older_than` days.
+    Gc {
+        #[arg(long = "older_than", default_value_t = 7)]
+        older_than: u64,
+        //remove the unfinished jobs too.
+        #[arg(long)]
+        all: bool,
+    },
+}
+
+pub async fn jobs(command: JobsCommand, config: &ClientConfig, json: bool) -> Result<()> {
+    let store = JobStore::open(&config.job_dir)?;
+    match command {
+        JobsCommand::List => {
+            let records = store.list()?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&records)?);
+            } else {
+                print_jobs(&records);
+            }
+        }
+        JobsCommand::Resume { to, concurrency } => {
+            let records = store.unfinished()?;
+            fetch(config, records, to.as_deref(), concurrency, json).await?;
+        }
+        JobsCommand::Gc { older_than, all } => {
+            let removed = store.gc(Duration::from_secs(older_than * 24 * 3600), all)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&removed)?);
+            } else {
+                println!("Removed {} jobs from {:?}", removed.len(), store.dir());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Waits for and downloads the results of recorded jobs, to `to` or to their output directory.
+pub async fn fetch(
+    config: &ClientConfig,
+    records: Vec<JobRecord>,
+    to: Option<&Path>,
+    concurrency: usize,
+    json: bool,
+) -> Result<()> {
+    if records.is_empty() {
+        if !json {
+            println!("No job to fetch");
+        }
+        return Ok(());
+    }
+    // the tasks may be finished already
+    let client = config.client()?.with_poll_policy(PollPolicy {
+        initial_delay: Duration::ZERO,
+        deadline: Duration::from_secs(config.poll_deadline),
+        ..Default::default()
+    });
+    let outcomes: Vec<_> = stream::iter(records)
+        .map(|record| fetch_one(&client, config, record, to))
+        .buffer_unordered(concurrency.max(1))
+        .collect()
+        .await;
+
+    let failed = outcomes.iter().filter(|o| o["error"].is_string()).count();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&outcomes)?);
+    } else {
+        for outcome in &outcomes {
+            let tx_hash = outcome["tx_hash"].as_str().unwrap_or_default();
+            match outcome["error"].as_str() {
+                Some(error) => println!("{} failed: {}", tx_hash, error),
+                None => println!("{} downloaded to {}", tx_hash, outcome["out_dir"]),
+            }
+        }
+    }
+    if failed > 0 {
+        bail!("{} jobs failed", failed);
+    }
+    Ok(())
+}
+
+async fn fetch_one(
+    client: &ProverClient,
+    config: &ClientConfig,
+    record: JobRecord,
+    to: Option<&Path>,
+) -> serde_json::Value {
+    let out_dir = to
+        .map(Path::to_path_buf)
+        .or_else(|| record.out_dir.clone())
+        .unwrap_or_else(|| config.out_dir.join(format!("chunk_{}", record.chunk_id)));
+    log::info!("Fetch {} to {:?}", record.tx_hash, out_dir);
+    let result = match client.resume(&record).await {
+        Ok(job) => job.fetch_results(&out_dir).await,
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(files) => json!({ "tx_hash": record.tx_hash, "out_dir": out_dir, "files": files }),
+        Err(e) => {
+            json!({ "tx_hash": record.tx_hash, "out_dir": out_dir, "error": format!("{:#}", e) })
+        }
+    }
+}
+
+fn print_jobs(records: &[JobRecord]) {
+    println!(
+        "{:<64}  {:<10}  {:>5}  {:<10}  result",
+        "tx hash", "task", "chunk", "state"
+    );
+    for record in records {
+        let result = match (&record.error, &record.out_dir) {
+            (Some(error), _) => error.clone(),
+            (None, Some(out_dir)) => format!("{:?}", out_dir),
+            (None, None) => String::new(),
+        };
+        println!(
+            "{:<64}  {:<10}  {:>5}  {:<10}  {}",
+            record.tx_hash,
+            record.task_name,
+            record.chunk_id,
+            format!("{:?}", record.state).to_lowercase(),
+            result
+        );
+    }
+}
@@ -0,0 +1,239 @@
+//! `eigen-gevulot`: chunk a task, stage and submit its chunks, follow and fetch the results,
+//! verify and inspect them, and deploy the prover and verifier images.
+//!
+//! The subcommands share a profile of `eigen-gevulot.toml` (see `images::config`), the
+//! `EIGEN_GEVULOT_*` environment variables and the global options override it, and `--json`
+//! prints machine-readable output.
+extern crate clap;
+use clap::{command, Parser, Subcommand};
+
+use anyhow::{anyhow, Result};
+use gevulot_cli::keyfile;
+use images::chunk::generate_chunk_files;
+use images::config::ConfigArgs;
+use images::deploy::{deploy, DeployConfig};
+use images::jobs::JobStore;
+use images::ops::OpsManifest;
+use serde_json::json;
+
+use std::fs;
+use std::path::PathBuf;
+
+mod inspect;
+mod jobs;
+mod status;
+mod submit;
+
+use inspect::InspectCommand;
+use jobs::JobsCommand;
+use submit::SubmitArgs;
+
+#[derive(Debug, Parser)]
+#[command(about, version)]
+struct Cli {
+    #[command(flatten)]
+    config: ConfigArgs,
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Split a task into the bootloader inputs of its chunks, <task>_chunks_<i>.data.
+    Chunk {
+        //the guest program, e.g. program/lr.
+        #[arg(long = "program_dir")]
+        program_dir: String,
+        #[arg(long = "trace_file", default_value = "solidityExample.json")]
+        trace_file: PathBuf,
+        #[arg(long = "task_name", default_value = "lr")]
+        task_name: String,
+        #[arg(long = "chunk_dir", default_value = ".")]
+        chunk_dir: PathBuf,
+    },
+    /// Stage files into the work path of the http file server.
+    Stage { files: Vec<PathBuf> },
+    /// Submit the chunks of a task, and with --wait download their results.
+    Submit(SubmitArgs),
+    /// Print the state of a transaction: pending, proving, verified or failed.
+    Status {
+        tx_hash: String,
+        //query the state until the job is verified or failed, printing every change.
+        #[arg(long)]
+        watch: bool,
+        //in seconds, with --watch.
+        #[arg(long = "interval", default_value_t = 10)]
+        interval: u64,
+    },
+    /// Print the transaction tree of a transaction, with the payload of the leaves.
+    Tree { tx_hash: String },
+    /// Wait for and download the results of submitted jobs.
+    Fetch {
+        tx_hashes: Vec<String>,
+        //the output directory of the submission by default.
+        #[arg(long = "to")]
+        to: Option<PathBuf>,
+        #[arg(long = "concurrency", default_value_t = 4)]
+        concurrency: usize,
+    },
+    /// Verify the downloaded results of a chunk: the manifest, the files and the zkin of the proof.
+    /// The starky proof itself isn't verified, the verifier program does that on Gevulot.
+    #[command(visible_alias = "check")]
+    Verify {
+        dir: PathBuf,
+        #[arg(long = "task_name", default_value = "lr")]
+        task_name: String,
+        #[arg(long = "chunk_id", default_value_t = 0)]
+        chunk_id: usize,
+    },
+    /// Deploy the prover and verifier images and print their program hashes.
+    Deploy {
+        #[arg(long = "deploy_config", default_value = "deploy.json")]
+        deploy_config: PathBuf,
+        //the program hashes are also written to this file.
+        #[arg(long = "output")]
+        output: Option<PathBuf>,
+    },
+    /// Generate the OPS image manifest of the prover or the verifier.
+    Manifest {
+        #[arg(long = "program", default_value = "gevulot-prover")]
+        program: String,
+        #[arg(long = "rust_log", default_value = "info")]
+        rust_log: String,
+        //the powdr std directory, relative to the packaging directory, e.g. powdr.
+        #[arg(long = "powdr_std")]
+        powdr_std: Option<PathBuf>,
+        //extra environment variables, NAME=VALUE.
+        #[arg(long = "env", value_parser = parse_pair)]
+        env: Vec<(String, String)>,
+        //extra mounts, e.g. %2=/data.
+        #[arg(long = "mount", value_parser = parse_pair)]
+        mounts: Vec<(String, String)>,
+        //extra files packaged into the image, e.g. gevulot/starkStruct.json.
+        #[arg(long = "file")]
+        files: Vec<PathBuf>,
+        //the manifest is printed if no output is given.
+        #[arg(long = "output")]
+        output: Option<PathBuf>,
+    },
+    /// Print the effective configuration: the profile with the overrides.
+    Config,
+    /// Inspect a starky proof.
+    #[command(subcommand)]
+    Inspect(InspectCommand),
+    /// List, resume and clean up the recorded jobs.
+    #[command(subcommand)]
+    Jobs(JobsCommand),
+}
+
+fn parse_pair(s: &str) -> std::result::Result<(String, String), String> {
+    let (name, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected <name>=<value>, got {}", s))?;
+    Ok((name.to_string(), value.to_string()))
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    env_logger::init();
+    let cli = Cli::parse();
+    let json = cli.json;
+    let config = cli.config.load()?;
+
+    match cli.command {
+        Command::Chunk {
+            program_dir,
+            trace_file,
+            task_name,
+            chunk_dir,
+        } => {
+            let files = generate_chunk_files(&program_dir, &trace_file, &task_name, &chunk_dir)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&files)?);
+            } else {
+                for file in &files {
+                    println!("{}", file.display());
+                }
+            }
+        }
+        Command::Stage { files } => submit::stage(&config, &files, json).await?,
+        Command::Submit(args) => submit::submit(&config, args, json).await?,
+        Command::Status {
+            tx_hash,
+            watch,
+            interval,
+        } => status::status(&config, &tx_hash, watch, interval, json).await?,
+        Command::Tree { tx_hash } => status::tree(&config, &tx_hash, json).await?,
+        Command::Fetch {
+            tx_hashes,
+            to,
+            concurrency,
+        } => {
+            let store = JobStore::open(&config.job_dir)?;
+            let records = tx_hashes
+                .iter()
+                .map(|hash| store.load(hash))
+                .collect::<Result<Vec<_>>>()?;
+            jobs::fetch(&config, records, to.as_deref(), concurrency, json).await?;
+        }
+        Command::Verify {
+            dir,
+            task_name,
+            chunk_id,
+        } => inspect::verify(&dir, &task_name, chunk_id, json)?,
+        Command::Deploy {
+            deploy_config,
+            output,
+        } => {
+            let deploy_config = DeployConfig::load(&deploy_config)?;
+            let key = keyfile::read_key_file(&config.key_file)
+                .map_err(|err| anyhow!("read the key file {:?} error: {}", config.key_file, err))?;
+            let client = config.client()?;
+            let deployment = deploy(client.rpc(), &key, &deploy_config).await?;
+            let deployment = serde_json::to_string_pretty(&deployment)?;
+            if let Some(output) = &output {
+                fs::write(output, &deployment)?;
+            }
+            println!("{}", deployment);
+        }
+        Command::Manifest {
+            program,
+            rust_log,
+            powdr_std,
+            env,
+            mounts,
+            files,
+            output,
+        } => {
+            let mut manifest = OpsManifest::new(&program, &rust_log);
+            if let Some(std_dir) = &powdr_std {
+                manifest = manifest.with_powdr_std(std_dir)?;
+            }
+            manifest.env.extend(env);
+            manifest.mounts.extend(mounts);
+            for file in &files {
+                manifest.add_file(file)?;
+            }
+            match &output {
+                Some(output) => {
+                    manifest.save(output)?;
+                    log::info!("{} files in {:?}", manifest.files.len(), output);
+                }
+                None => println!("{}", serde_json::to_string_pretty(&manifest)?),
+            }
+        }
+        Command::Config => {
+            if json {
+                let profile = json!({ "profile": config.profile, "config": config });
+                println!("{}", serde_json::to_string_pretty(&profile)?);
+            } else {
+                print!("{}", config.to_toml()?);
+            }
+        }
+        Command::Inspect(command) => inspect::inspect(command, json)?,
+        Command::Jobs(command) => jobs::jobs(command, &config, json).await?,
+    }
+    Ok(())
+}
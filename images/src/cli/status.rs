@@ -0,0 +1,130 @@
+//! `status` and `tree`: the transaction tree of a submitted job and its derived state.
+use anyhow::{bail, Result};
+use images::client::parse_hash;
+use images::config::ClientConfig;
+use images::jobs::{JobRecord, JobStore};
+use images::poll::PollPolicy;
+use images::status::{tx_status, watch_tx_status, TxState, TxStatus};
+use serde_json::json;
+
+use std::time::Duration;
+
+/// Prints the state of a transaction once, or with `watch` every time it changes until the job
+/// is verified or failed. With `json` every status is one JSON line while watching.
+pub async fn status(
+    config: &ClientConfig,
+    tx_hash: &str,
+    watch: bool,
+    interval: u64,
+    json: bool,
+) -> Result<()> {
+    let hash = parse_hash("tx_hash", Some(tx_hash.to_string()))?;
+    let record = JobStore::open(&config.job_dir)?.load(tx_hash).ok();
+    let verifier_hash = verifier_hash(config, record.as_ref());
+    let client = config.client()?;
+
+    let status = if watch {
+        let policy = PollPolicy {
+            initial_delay: Duration::ZERO,
+            interval: Duration::from_secs(interval),
+            max_interval: Duration::from_secs(interval),
+            multiplier: 1.0,
+            jitter: 0.0,
+            deadline: Duration::from_secs(config.poll_deadline),
+        };
+        let mut printed = Ok(());
+        let status = watch_tx_status(
+            client.rpc(),
+            &hash,
+            verifier_hash.as_deref(),
+            &policy,
+            |status| {
+                if printed.is_ok() {
+                    printed = print_status(status, record.as_ref(), json, false);
+                }
+            },
+        )
+        .await;
+        printed?;
+        status
+    } else {
+        let status = tx_status(client.rpc(), &hash, verifier_hash.as_deref()).await;
+        print_status(&status, record.as_ref(), json, true)?;
+        status
+    };
+
+    if status.state == TxState::Failed {
+        bail!(
+            "{} failed: {}",
+            tx_hash,
+            status.error.as_deref().unwrap_or_default()
+        );
+    }
+    Ok(())
+}
+
+/// Prints the transaction tree with the payload of the leaves.
+pub async fn tree(config: &ClientConfig, tx_hash: &str, json: bool) -> Result<()> {
+    let hash = parse_hash("tx_hash", Some(tx_hash.to_string()))?;
+    let record = JobStore::open(&config.job_dir)?.load(tx_hash).ok();
+    let verifier_hash = verifier_hash(config, record.as_ref());
+    let client = config.client()?;
+    let status = tx_status(client.rpc(), &hash, verifier_hash.as_deref()).await;
+    match (&status.tree, json) {
+        (Some(tree), true) => println!("{}", serde_json::to_string_pretty(tree)?),
+        (Some(tree), false) => print!("{}", tree),
+        (None, _) => bail!(
+            "no transaction tree for {}: {}",
+            tx_hash,
+            status.error.as_deref().unwrap_or_default()
+        ),
+    }
+    Ok(())
+}
+
+// The verifier of the recorded job, or of the config.
+fn verifier_hash(config: &ClientConfig, record: Option<&JobRecord>) -> Option<String> {
+    match record {
+        Some(record) => Some(record.verifier_hash.clone()),
+        None => config.verifier_hash.clone(),
+    }
+}
+
+fn print_status(
+    status: &TxStatus,
+    record: Option<&JobRecord>,
+    json: bool,
+    pretty: bool,
+) -> Result<()> {
+    let job = record.map(|record| record.state);
+    if json {
+        let status = json!({ "status": status, "job": job });
+        if pretty {
+            println!("{}", serde_json::to_string_pretty(&status)?);
+        } else {
+            println!("{}", serde_json::to_string(&status)?);
+        }
+        return Ok(());
+    }
+    let mut line = format!("{}: {}", status.tx_hash, status.state);
+    if let Some(tree) = &status.tree {
+        line.push_str(&format!(
+            ", {} of {} leaves verified",
+            status.verified_leaves,
+            tree.leaves().len()
+        ));
+    }
+    if let Some(error) = &status.error {
+        line.push_str(&format!(" ({})", error));
+    }
+    println!("{}", line);
+    if let Some(record) = record {
+        println!(
+            "  {} chunk {}, job {}",
+            record.task_name,
+            record.chunk_id,
+            format!("{:?}", record.state).to_lowercase()
+        );
+    }
+    Ok(())
+}
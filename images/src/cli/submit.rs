@@ -0,0 +1,178 @@
+//! `stage` and `submit`: the proving requests of the chunks of a task.
+use anyhow::{bail, Result};
+use clap::Args;
+use futures_util::stream::{self, StreamExt};
+use images::batch::{find_chunks, prove_chunks, BatchOptions, BatchSummary, ChunkInput};
+use images::circom::CircomOverrides;
+use images::client::ProvingRequest;
+use images::config::ClientConfig;
+use images::stage::{HashCache, StagingArea};
+use serde_json::json;
+
+use std::path::PathBuf;
+
+#[derive(Debug, Args)]
+pub struct SubmitArgs {
+    #[arg(long = "task_name", default_value = "lr")]
+    task_name: String,
+    #[arg(long = "trace_file", default_value = "solidityExample.json")]
+    trace_file: PathBuf,
+    #[arg(long = "asm_file", default_value = "lr.asm")]
+    asm_file: PathBuf,
+    //a single chunk, otherwise every <task>_chunks_<i>.data file of chunk_dir.
+    #[arg(long = "bi_file")]
+    bi_file: Option<PathBuf>,
+    #[arg(long = "chunk_id", default_value_t = 0)]
+    chunk_id: usize,
+    #[arg(long = "chunk_dir", default_value = ".")]
+    chunk_dir: PathBuf,
+    //wait for the results and download chunk i to <out_dir>/chunk_<i>, with the summary.json of the batch.
+    #[arg(long)]
+    wait: bool,
+    #[arg(long = "concurrency", default_value_t = 4)]
+    concurrency: usize,
+    //how many times the failed chunks are submitted again, with --wait.
+    #[arg(long = "retries", default_value_t = 0)]
+    retries: u32,
+    //only submit the failed chunks of the summary.json in out_dir.
+    #[arg(long = "resubmit_failed")]
+    resubmit_failed: bool,
+    //the circom verifier options passed to the prover, e.g. --agg_stage true.
+    #[command(flatten)]
+    circom: CircomOverrides,
+}
+
+/// Stages local files into the work path of the http file server and prints their URLs.
+pub async fn stage(config: &ClientConfig, files: &[PathBuf], json: bool) -> Result<()> {
+    let area = StagingArea::new(&config.http_server_work_path, &config.http_url)?;
+    let hashes = HashCache::new();
+    let mut staged = vec![];
+    for file in files {
+        let file = area.stage(file, &hashes).await?;
+        staged.push(json!({
+            "source": file.source,
+            "path": file.path,
+            "checksum": file.checksum,
+            "url": file.url.to_string(),
+        }));
+    }
+    if json {
+        println!("{}", serde_json::to_string_pretty(&staged)?);
+    } else {
+        for file in &staged {
+            println!(
+                "{} {}",
+                file["checksum"].as_str().unwrap_or_default(),
+                file["url"].as_str().unwrap_or_default()
+            );
+        }
+    }
+    Ok(())
+}
+
+pub async fn submit(config: &ClientConfig, args: SubmitArgs, json: bool) -> Result<()> {
+    let previous = if args.resubmit_failed {
+        Some(BatchSummary::load(&config.out_dir)?)
+    } else {
+        None
+    };
+    let chunks = match (&previous, &args.bi_file) {
+        (Some(summary), _) => summary.failed_chunks(),
+        (None, Some(bi_file)) => vec![ChunkInput {
+            chunk_id: args.chunk_id,
+            bi_file: bi_file.clone(),
+        }],
+        (None, None) => find_chunks(&args.chunk_dir, &args.task_name)?,
+    };
+    let Some(first) = chunks.first() else {
+        if !json {
+            println!("No chunk to submit");
+        }
+        return Ok(());
+    };
+    if config.serve.is_some() && !args.wait {
+        bail!("the embedded file server only runs while waiting, add --wait");
+    }
+    log::info!("Submit {} chunks of {}", chunks.len(), args.task_name);
+
+    let mut builder = ProvingRequest::builder()
+        .trace_file(&args.trace_file)
+        .bi_file(&first.bi_file)
+        .asm_file(&args.asm_file)
+        .task_name(&args.task_name)
+        .http_server_work_path(&config.http_server_work_path)
+        .http_url(&config.http_url)
+        .circom(args.circom);
+    let programs = config.programs(&args.task_name);
+    if let Some(hash) = programs.prover_hash {
+        builder = builder.prover_hash(hash);
+    }
+    if let Some(hash) = programs.verifier_hash {
+        builder = builder.verifier_hash(hash);
+    }
+    let template = builder.build()?;
+    let client = config.client()?;
+
+    if !args.wait {
+        let submitted: Vec<_> = stream::iter(chunks)
+            .map(|chunk| {
+                let mut request = template.clone();
+                request.bi_file = chunk.bi_file;
+                request.chunk_id = chunk.chunk_id;
+                let client = &client;
+                async move {
+                    let chunk_id = request.chunk_id;
+                    match client.submit(request).await {
+                        Ok(job) => {
+                            json!({ "chunk_id": chunk_id, "tx_hash": job.tx_hash().to_string() })
+                        }
+                        Err(e) => json!({ "chunk_id": chunk_id, "error": format!("{:#}", e) }),
+                    }
+                }
+            })
+            .buffer_unordered(args.concurrency.max(1))
+            .collect()
+            .await;
+        let failed = submitted.iter().filter(|s| s["error"].is_string()).count();
+        if json {
+            println!("{}", serde_json::to_string_pretty(&submitted)?);
+        } else {
+            for s in &submitted {
+                match s["error"].as_str() {
+                    Some(error) => println!("chunk {}: failed: {}", s["chunk_id"], error),
+                    None => println!(
+                        "chunk {}: tx {}",
+                        s["chunk_id"],
+                        s["tx_hash"].as_str().unwrap_or_default()
+                    ),
+                }
+            }
+        }
+        if failed > 0 {
+            bail!("{} chunks failed to submit", failed);
+        }
+        return Ok(());
+    }
+
+    let options = BatchOptions {
+        concurrency: args.concurrency,
+        out_dir: config.out_dir.clone(),
+        retries: args.retries,
+    };
+    let summary = prove_chunks(&client, &template, chunks, previous, &options).await?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+    } else {
+        summary.print();
+    }
+
+    let failed = summary.failed().count();
+    if failed > 0 {
+        bail!(
+            "{} chunks failed, see {:?}",
+            failed,
+            BatchSummary::path(&config.out_dir)
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,479 @@
+//! A typed client to submit a proving task to Gevulot and collect its results.
+//!
+//! ```ignore
+//! let request = ProvingRequest::builder()
+//!     .prover_hash(prover_hash)
+//!     .verifier_hash(verifier_hash)
+//!     .bi_file("lr_chunks_0.data")
+//!     .http_url("http://4.145.88.10:8080")
+//!     .build()?;
+//! let client = ProverClient::new("http://localhost:9944", Path::new("localkey.pki"), None)?;
+//! let job = client.submit(request).await?;
+//! let files = job.fetch_results(Path::new("/tmp/gevulot")).await?;
+//! ```
+use anyhow::{anyhow, bail, Context, Result};
+use gevulot_cli::keyfile;
+use gevulot_node::rpc_client::{RpcClient, RpcClientBuilder};
+use gevulot_node::types::{Hash, TransactionTree};
+use libsecp256k1::SecretKey;
+
+use tokio::sync::Mutex;
+use url::Url;
+
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::backend::ProvingBackend;
+use crate::circom::CircomOverrides;
+use crate::download::{DownloadManager, DownloadOptions, DownloadRequest};
+use crate::file::{call_rpc_prover, get_verification_leaves, select_leaf, wait_for_tx_tree};
+use crate::jobs::{JobRecord, JobStore};
+use crate::poll::PollPolicy;
+use crate::server::FileServer;
+use crate::stage::{HashCache, StagedInputs, StagingArea};
+use crate::status::{tx_status, watch_tx_status, TxStatus};
+
+/// One chunk to prove. The input files are local files, staged into `http_server_work_path`,
+/// which is served at `http_url`, when the request is submitted.
+#[derive(Debug, Clone)]
+pub struct ProvingRequest {
+    pub prover_hash: Hash,
+    pub verifier_hash: Hash,
+    pub trace_file: PathBuf,
+    pub bi_file: PathBuf,
+    pub asm_file: PathBuf,
+    pub task_name: String,
+    pub chunk_id: usize,
+    pub http_server_work_path: PathBuf,
+    pub http_url: String,
+    /// Passed to the prover, the circom config of the prover applies otherwise.
+    pub circom: CircomOverrides,
+}
+
+impl ProvingRequest {
+    pub fn builder() -> ProvingRequestBuilder {
+        ProvingRequestBuilder::default()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ProvingRequestBuilder {
+    prover_hash: Option<String>,
+    verifier_hash: Option<String>,
+    trace_file: Option<PathBuf>,
+    bi_file: Option<PathBuf>,
+    asm_file: Option<PathBuf>,
+    task_name: Option<String>,
+    chunk_id: Option<usize>,
+    http_server_work_path: Option<PathBuf>,
+    http_url: Option<String>,
+    circom: CircomOverrides,
+}
+
+impl ProvingRequestBuilder {
+    /// The hex hash of the deployed prover program.
+    pub fn prover_hash<S: Into<String>>(mut self, hash: S) -> Self {
+        self.prover_hash = Some(hash.into());
+        self
+    }
+
+    /// The hex hash of the deployed verifier program.
+    pub fn verifier_hash<S: Into<String>>(mut self, hash: S) -> Self {
+        self.verifier_hash = Some(hash.into());
+        self
+    }
+
+    pub fn trace_file<P: Into<PathBuf>>(mut self, file: P) -> Self {
+        self.trace_file = Some(file.into());
+        self
+    }
+
+    pub fn bi_file<P: Into<PathBuf>>(mut self, file: P) -> Self {
+        self.bi_file = Some(file.into());
+        self
+    }
+
+    pub fn asm_file<P: Into<PathBuf>>(mut self, file: P) -> Self {
+        self.asm_file = Some(file.into());
+        self
+    }
+
+    pub fn task_name<S: Into<String>>(mut self, name: S) -> Self {
+        self.task_name = Some(name.into());
+        self
+    }
+
+    pub fn chunk_id(mut self, chunk_id: usize) -> Self {
+        self.chunk_id = Some(chunk_id);
+        self
+    }
+
+    pub fn http_server_work_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.http_server_work_path = Some(path.into());
+        self
+    }
+
+    pub fn http_url<S: Into<String>>(mut self, url: S) -> Self {
+        self.http_url = Some(url.into());
+        self
+    }
+
+    pub fn circom(mut self, circom: CircomOverrides) -> Self {
+        self.circom = circom;
+        self
+    }
+
+    /// Checks the request. The defaults are the lr example: `solidityExample.json`, `lr.asm`,
+    /// the task `lr`, the chunk 0 and the work path `/data/http/`. A relative input file which
+    /// doesn't exist is looked up in the work path.
+    pub fn build(self) -> Result<ProvingRequest> {
+        let prover_hash = parse_hash("prover_hash", self.prover_hash)?;
+        let verifier_hash = parse_hash("verifier_hash", self.verifier_hash)?;
+        let http_url = self
+            .http_url
+            .ok_or_else(|| anyhow!("http_url is required"))?;
+        let http_server_work_path = self
+            .http_server_work_path
+            .unwrap_or_else(|| PathBuf::from("/data/http/"));
+        // checks the url
+        StagingArea::new(&http_server_work_path, &http_url)?;
+
+        let resolve = |name: &str, file: Option<PathBuf>| -> Result<PathBuf> {
+            let file = file.ok_or_else(|| anyhow!("{} is required", name))?;
+            if file.is_file() {
+                return Ok(file);
+            }
+            let served = http_server_work_path.join(&file);
+            if file.is_relative() && served.is_file() {
+                return Ok(served);
+            }
+            bail!("the {} {:?} doesn't exist", name, file)
+        };
+        let trace_file = resolve(
+            "trace_file",
+            self.trace_file.or(Some("solidityExample.json".into())),
+        )?;
+        let bi_file = resolve("bi_file", self.bi_file)?;
+        let asm_file = resolve("asm_file", self.asm_file.or(Some("lr.asm".into())))?;
+
+        let task_name = self.task_name.unwrap_or_else(|| "lr".to_string());
+        if task_name.is_empty() || task_name.contains('/') {
+            bail!("invalid task_name `{}`", task_name);
+        }
+        Ok(ProvingRequest {
+            prover_hash,
+            verifier_hash,
+            trace_file,
+            bi_file,
+            asm_file,
+            task_name,
+            chunk_id: self.chunk_id.unwrap_or(0),
+            http_server_work_path,
+            http_url,
+            circom: self.circom,
+        })
+    }
+}
+
+/// Parses a 32 bytes hex hash, `name` is used in the errors.
+pub fn parse_hash(name: &str, hash: Option<String>) -> Result<Hash> {
+    let hash = hash.ok_or_else(|| anyhow!("{} is required", name))?;
+    let bytes = hex::decode(hash.trim()).with_context(|| format!("invalid {} {}", name, hash))?;
+    if bytes.len() != 32 {
+        bail!(
+            "invalid {} {}: expected 32 bytes, got {}",
+            name,
+            hash,
+            bytes.len()
+        );
+    }
+    Ok((&bytes[..]).into())
+}
+
+/// Submits proving requests to a Gevulot node, through the `RpcClient` by default.
+pub struct ProverClient<B = RpcClient> {
+    backend: B,
+    key: SecretKey,
+    poll_policy: PollPolicy,
+    download_options: DownloadOptions,
+    hashes: HashCache,
+    serve_addr: Option<SocketAddr>,
+    server: Mutex<Option<FileServer>>,
+    jobs: Option<JobStore>,
+}
+
+impl ProverClient<RpcClient> {
+    pub fn new(json_rpc_url: &str, keyfile: &Path, rpc_timeout: Option<Duration>) -> Result<Self> {
+        let key = keyfile::read_key_file(&keyfile.to_path_buf())
+            .map_err(|err| anyhow!("read the key file {:?} error: {}", keyfile, err))?;
+        let mut builder = RpcClientBuilder::default();
+        if let Some(timeout) = rpc_timeout {
+            builder = builder.request_timeout(timeout);
+        }
+        let rpc = builder
+            .build(json_rpc_url.to_owned())
+            .map_err(|err| anyhow!("build the rpc client of {} error: {}", json_rpc_url, err))?;
+        Ok(ProverClient::with_backend(rpc, key))
+    }
+
+    pub fn rpc(&self) -> &RpcClient {
+        &self.backend
+    }
+}
+
+impl<B: ProvingBackend> ProverClient<B> {
+    /// A client talking to any node `backend`, e.g. a `MemoryBackend`.
+    pub fn with_backend(backend: B, key: SecretKey) -> Self {
+        ProverClient {
+            backend,
+            key,
+            poll_policy: PollPolicy::default(),
+            download_options: DownloadOptions::default(),
+            hashes: HashCache::new(),
+            serve_addr: None,
+            server: Mutex::new(None),
+            jobs: None,
+        }
+    }
+
+    pub fn with_poll_policy(mut self, poll_policy: PollPolicy) -> Self {
+        self.poll_policy = poll_policy;
+        self
+    }
+
+    pub fn with_download_options(mut self, download_options: DownloadOptions) -> Self {
+        self.download_options = download_options;
+        self
+    }
+
+    /// Serves the staged inputs with the embedded file server bound to `addr`, until the node
+    /// has fetched them. The `http_url` of the requests must be the public URL of `addr`.
+    pub fn with_file_server(mut self, addr: SocketAddr) -> Self {
+        self.serve_addr = Some(addr);
+        self
+    }
+
+    /// Records every submitted job and its results in `jobs`, see `resume`.
+    pub fn with_job_store(mut self, jobs: JobStore) -> Self {
+        self.jobs = Some(jobs);
+        self
+    }
+
+    pub fn job_store(&self) -> Option<&JobStore> {
+        self.jobs.as_ref()
+    }
+
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// Stages the input files and sends the prover and verifier workflow of the request.
+    pub async fn submit(&self, request: ProvingRequest) -> Result<ProvingJob<'_, B>> {
+        let inputs = StagedInputs::stage(&request, &self.hashes).await?;
+        if let Some(addr) = self.serve_addr {
+            let paths = inputs.files().map(|f| f.url.path().to_string());
+            self.serve(&request, paths, addr).await?;
+        }
+        let tx_hash = call_rpc_prover(&self.backend, &self.key, &request, &inputs)
+            .await
+            .map_err(|err| anyhow!("submit chunk {}: {}", request.chunk_id, err))?;
+        log::info!("Submitted chunk {} in {}", request.chunk_id, tx_hash);
+        if let Some(jobs) = &self.jobs {
+            // the transaction is sent, the job goes on without its record
+            let served = self.serve_addr.is_some();
+            let record = JobRecord::new(&tx_hash.to_string(), &request, &inputs, served);
+            if let Err(e) = jobs.save(&record) {
+                log::warn!(
+                    "record the job {} of chunk {} error: {:#}",
+                    tx_hash,
+                    request.chunk_id,
+                    e
+                );
+            }
+        }
+        Ok(ProvingJob {
+            client: self,
+            request,
+            tx_hash,
+        })
+    }
+
+    /// Queries the transaction tree of `tx_hash` and its leaves once, see `status::tx_status`.
+    pub async fn status_of(&self, tx_hash: &Hash, verifier_hash: Option<&str>) -> TxStatus {
+        tx_status(&self.backend, tx_hash, verifier_hash).await
+    }
+
+    /// The job of a recorded submission, to wait for and download its results again. The inputs
+    /// of a job served by the embedded file server are served again, so the client must have a
+    /// file server too.
+    pub async fn resume(&self, record: &JobRecord) -> Result<ProvingJob<'_, B>> {
+        let request = ProvingRequest {
+            prover_hash: parse_hash("prover_hash", Some(record.prover_hash.clone()))?,
+            verifier_hash: parse_hash("verifier_hash", Some(record.verifier_hash.clone()))?,
+            trace_file: record.trace.path.clone(),
+            bi_file: record.bi.path.clone(),
+            asm_file: record.asm.path.clone(),
+            task_name: record.task_name.clone(),
+            chunk_id: record.chunk_id,
+            http_server_work_path: record.http_server_work_path.clone(),
+            http_url: record.http_url.clone(),
+            circom: record.circom,
+        };
+        if record.served {
+            let Some(addr) = self.serve_addr else {
+                bail!(
+                    "the inputs of {} were served by the embedded file server, resume it with --serve",
+                    record.tx_hash
+                );
+            };
+            let paths = [&record.trace, &record.bi, &record.asm]
+                .into_iter()
+                .map(|input| {
+                    let url = Url::parse(&input.url)
+                        .with_context(|| format!("invalid input url {}", input.url))?;
+                    Ok(url.path().to_string())
+                })
+                .collect::<Result<Vec<_>>>()?;
+            self.serve(&request, paths, addr).await?;
+        }
+        Ok(ProvingJob {
+            client: self,
+            request,
+            tx_hash: parse_hash("tx_hash", Some(record.tx_hash.clone()))?,
+        })
+    }
+
+    // Starts the file server if it has stopped, and adds the URL paths of the inputs to the files
+    // to serve.
+    async fn serve<I: IntoIterator<Item = String>>(
+        &self,
+        request: &ProvingRequest,
+        paths: I,
+        addr: SocketAddr,
+    ) -> Result<()> {
+        let area = StagingArea::new(&request.http_server_work_path, &request.http_url)?;
+        if area.base_url.path() != "/" {
+            bail!(
+                "the http_url {} of the embedded file server must not have a path",
+                request.http_url
+            );
+        }
+        let mut server = self.server.lock().await;
+        match server.as_ref() {
+            Some(s) if s.is_running() => {
+                if s.root() != request.http_server_work_path {
+                    bail!(
+                        "the file server serves {:?}, not {:?}",
+                        s.root(),
+                        request.http_server_work_path
+                    );
+                }
+            }
+            _ => *server = Some(FileServer::start(&request.http_server_work_path, addr).await?),
+        }
+        if let Some(s) = server.as_ref() {
+            s.expect(paths);
+        }
+        Ok(())
+    }
+}
+
+/// A submitted request, identified by the hash of its Run transaction.
+pub struct ProvingJob<'a, B = RpcClient> {
+    client: &'a ProverClient<B>,
+    request: ProvingRequest,
+    tx_hash: Hash,
+}
+
+impl<B: ProvingBackend> ProvingJob<'_, B> {
+    pub fn tx_hash(&self) -> Hash {
+        self.tx_hash
+    }
+
+    pub fn request(&self) -> &ProvingRequest {
+        &self.request
+    }
+
+    /// Queries the transaction tree once, a leaf is verified if it's a verification of the
+    /// verifier of the request.
+    pub async fn status(&self) -> TxStatus {
+        let verifier_hash = self.request.verifier_hash.to_string();
+        self.client
+            .status_of(&self.tx_hash, Some(&verifier_hash))
+            .await
+    }
+
+    /// Queries the status according to the poll policy of the client until the job is verified
+    /// or failed, see `status::watch_tx_status`.
+    pub async fn watch(&self, on_change: impl FnMut(&TxStatus)) -> TxStatus {
+        let verifier_hash = self.request.verifier_hash.to_string();
+        watch_tx_status(
+            &self.client.backend,
+            &self.tx_hash,
+            Some(&verifier_hash),
+            &self.client.poll_policy,
+            on_change,
+        )
+        .await
+    }
+
+    /// Waits for a verification leaf of the verifier of the request according to the poll
+    /// policy of the client.
+    pub async fn wait(&self) -> Result<TransactionTree> {
+        let verifier_hash = self.request.verifier_hash.to_string();
+        Ok(wait_for_tx_tree(
+            &self.client.backend,
+            &self.tx_hash,
+            Some(&verifier_hash),
+            &self.client.poll_policy,
+        )
+        .await?)
+    }
+
+    /// Waits for the verification and downloads the files of the selected leaf to `out_dir`.
+    /// The outcome is recorded in the job store of the client.
+    pub async fn fetch_results(&self, out_dir: &Path) -> Result<Vec<PathBuf>> {
+        let result = self.download_results(out_dir).await;
+        if let Some(jobs) = &self.client.jobs {
+            if let Err(e) = self.record(jobs, out_dir, &result) {
+                log::warn!("record the job {} error: {:#}", self.tx_hash, e);
+            }
+        }
+        result
+    }
+
+    fn record(&self, jobs: &JobStore, out_dir: &Path, result: &Result<Vec<PathBuf>>) -> Result<()> {
+        let mut record = jobs.load(&self.tx_hash.to_string())?;
+        match result {
+            Ok(files) => record.downloaded(out_dir, files.clone()),
+            Err(e) => record.failed(out_dir, format!("{:#}", e)),
+        }
+        jobs.save(&record)
+    }
+
+    async fn download_results(&self, out_dir: &Path) -> Result<Vec<PathBuf>> {
+        let tree = self.wait().await?;
+        let leaves = get_verification_leaves(&self.client.backend, &tree)
+            .await
+            .map_err(|err| anyhow!("{}", err))?;
+        let verifier_hash = self.request.verifier_hash.to_string();
+        let leaf = select_leaf(leaves, &verifier_hash).map_err(|err| anyhow!("{}", err))?;
+        log::info!("Download the results of the leaf: {}", leaf.hash);
+
+        let mut requests = vec![];
+        for file in leaf.verification.files {
+            log::info!("File URL: {}, VM Path: {}", file.url, file.vm_path);
+            let file_name = Path::new(&file.vm_path)
+                .file_name()
+                .ok_or_else(|| anyhow!("no file name in {}", file.vm_path))?;
+            requests.push(DownloadRequest {
+                url: file.url,
+                dest: out_dir.join(file_name),
+                checksum: Some(file.checksum),
+            });
+        }
+        let manager = DownloadManager::new(self.client.download_options.clone())?;
+        manager.download_all(requests).await.into_iter().collect()
+    }
+}
@@ -0,0 +1,407 @@
+//! The configuration of the client, a profile of `eigen-gevulot.toml`:
+//!
+//! ```toml
+//! default_profile = "devnet"
+//!
+//! [profiles.local]
+//! json_rpc_url = "http://127.0.0.1:9944"
+//! http_url = "http://127.0.0.1:8080"
+//! serve = "127.0.0.1:8080"
+//!
+//! [profiles.devnet]
+//! json_rpc_url = "http://api.devnet.gevulot.com:9944"
+//! http_url = "http://4.145.88.10:8080"
+//!
+//! [profiles.devnet.tasks.lr]
+//! prover_hash = "735dd3a758ca4a7ddb17965a016068e789424eced65727549014f159e929cfa4"
+//! verifier_hash = "2390dcb2a644823ffe63e5a0e586cf20e69ab02479b011ece3b46614189a4cff"
+//! ```
+//!
+//! The `EIGEN_GEVULOT_*` environment variables override the profile, and the command line
+//! options override both (see `ConfigArgs`).
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Args;
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::client::ProverClient;
+use crate::download::DownloadOptions;
+use crate::jobs::JobStore;
+use crate::poll::PollPolicy;
+
+pub const DEFAULT_CONFIG_FILE: &str = "eigen-gevulot.toml";
+
+/// The deployed programs proving a task.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct TaskPrograms {
+    pub prover_hash: Option<String>,
+    pub verifier_hash: Option<String>,
+}
+
+/// A profile: the node, the file server, the programs of the tasks and the timeouts of the client.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+// a misspelled field is an error rather than silently replaced by its default
+#[serde(default, deny_unknown_fields)]
+pub struct ClientConfig {
+    /// The name of the profile, none for the defaults.
+    #[serde(skip)]
+    pub profile: Option<String>,
+    pub json_rpc_url: String,
+    pub key_file: PathBuf,
+    /// In seconds.
+    pub rpc_timeout: Option<u64>,
+    /// The public URL of the http file server serving `http_server_work_path`.
+    pub http_url: String,
+    pub http_server_work_path: PathBuf,
+    /// The bind address of the embedded file server, none to use an external one.
+    pub serve: Option<SocketAddr>,
+    /// The programs of the tasks without an entry in `tasks`.
+    pub prover_hash: Option<String>,
+    pub verifier_hash: Option<String>,
+    pub out_dir: PathBuf,
+    pub job_dir: PathBuf,
+    /// The longest wait for the verification, in seconds.
+    pub poll_deadline: u64,
+    /// The longest wait for the next chunk of a result file, in seconds.
+    pub download_timeout: u64,
+    pub tasks: BTreeMap<String, TaskPrograms>,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        ClientConfig {
+            profile: None,
+            json_rpc_url: "http://localhost:9944".to_string(),
+            key_file: PathBuf::from("localkey.pki"),
+            rpc_timeout: None,
+            http_url: "http://localhost:8080".to_string(),
+            http_server_work_path: PathBuf::from("/data/http/"),
+            serve: None,
+            prover_hash: None,
+            verifier_hash: None,
+            out_dir: PathBuf::from("/tmp/gevulot/"),
+            job_dir: JobStore::default_dir(),
+            poll_deadline: 3600,
+            download_timeout: 60,
+            tasks: BTreeMap::new(),
+        }
+    }
+}
+
+/// The profiles of `eigen-gevulot.toml`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    /// The profile used when none is given.
+    pub default_profile: Option<String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, ClientConfig>,
+}
+
+impl ConfigFile {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path).with_context(|| format!("read {:?}", path))?;
+        toml::from_str(&content).with_context(|| format!("parse {:?}", path))
+    }
+
+    /// The profile `name`, or the default profile, or the defaults if there's no default profile.
+    pub fn profile(mut self, name: Option<&str>) -> Result<ClientConfig> {
+        let Some(name) = name.map(str::to_string).or(self.default_profile.take()) else {
+            return Ok(ClientConfig::default());
+        };
+        let mut config = self.profiles.remove(&name).ok_or_else(|| {
+            let known: Vec<_> = self.profiles.keys().map(String::as_str).collect();
+            anyhow!("unknown profile `{}`, the profiles are {:?}", name, known)
+        })?;
+        config.profile = Some(name);
+        Ok(config)
+    }
+}
+
+impl ClientConfig {
+    /// The programs of `task_name`, a field missing in its entry of `tasks` is the one of the
+    /// profile.
+    pub fn programs(&self, task_name: &str) -> TaskPrograms {
+        let task = self.tasks.get(task_name).cloned().unwrap_or_default();
+        TaskPrograms {
+            prover_hash: task.prover_hash.or_else(|| self.prover_hash.clone()),
+            verifier_hash: task.verifier_hash.or_else(|| self.verifier_hash.clone()),
+        }
+    }
+
+    /// The effective configuration, printable and loadable as a profile.
+    pub fn to_toml(&self) -> Result<String> {
+        let profile = self.profile.as_deref().unwrap_or("<defaults>");
+        Ok(format!(
+            "# profile: {}\n{}",
+            profile,
+            toml::to_string_pretty(self)?
+        ))
+    }
+
+    /// A client of the node with the polling, the downloads, the file server and the job store
+    /// of the config.
+    pub fn client(&self) -> Result<ProverClient> {
+        let client = ProverClient::new(
+            &self.json_rpc_url,
+            &self.key_file,
+            self.rpc_timeout.map(Duration::from_secs),
+        )?
+        .with_poll_policy(PollPolicy {
+            deadline: Duration::from_secs(self.poll_deadline),
+            ..Default::default()
+        })
+        .with_download_options(DownloadOptions {
+            chunk_timeout: Duration::from_secs(self.download_timeout),
+            ..Default::default()
+        })
+        .with_job_store(JobStore::open(&self.job_dir)?);
+        Ok(match self.serve {
+            Some(addr) => client.with_file_server(addr),
+            None => client,
+        })
+    }
+}
+
+/// The options selecting the profile and overriding it, each also read from its environment
+/// variable.
+#[derive(Debug, Clone, Default, Args)]
+pub struct ConfigArgs {
+    //eigen-gevulot.toml by default, if it exists.
+    #[arg(long = "config", env = "EIGEN_GEVULOT_CONFIG", global = true)]
+    pub config: Option<PathBuf>,
+    //the default_profile of the config file by default.
+    #[arg(long = "profile", env = "EIGEN_GEVULOT_PROFILE", global = true)]
+    pub profile: Option<String>,
+    #[arg(
+        long = "json_rpc_url",
+        env = "EIGEN_GEVULOT_JSON_RPC_URL",
+        global = true
+    )]
+    pub json_rpc_url: Option<String>,
+    #[arg(long = "key_file", env = "EIGEN_GEVULOT_KEY_FILE", global = true)]
+    pub key_file: Option<PathBuf>,
+    //in seconds.
+    #[arg(long = "rpc_timeout", env = "EIGEN_GEVULOT_RPC_TIMEOUT", global = true)]
+    pub rpc_timeout: Option<u64>,
+    #[arg(long = "http_url", env = "EIGEN_GEVULOT_HTTP_URL", global = true)]
+    pub http_url: Option<String>,
+    #[arg(
+        long = "http_server_work_path",
+        env = "EIGEN_GEVULOT_HTTP_SERVER_WORK_PATH",
+        global = true
+    )]
+    pub http_server_work_path: Option<PathBuf>,
+    //the bind address of the embedded file server, e.g. 0.0.0.0:8080, http_url must be its public url.
+    #[arg(long = "serve", env = "EIGEN_GEVULOT_SERVE", global = true)]
+    pub serve: Option<SocketAddr>,
+    //the programs of every task.
+    #[arg(long = "prover_hash", env = "EIGEN_GEVULOT_PROVER_HASH", global = true)]
+    pub prover_hash: Option<String>,
+    #[arg(
+        long = "verifier_hash",
+        env = "EIGEN_GEVULOT_VERIFIER_HASH",
+        global = true
+    )]
+    pub verifier_hash: Option<String>,
+    #[arg(long = "out_dir", env = "EIGEN_GEVULOT_OUT_DIR", global = true)]
+    pub out_dir: Option<PathBuf>,
+    #[arg(long = "job_dir", env = "EIGEN_GEVULOT_JOB_DIR", global = true)]
+    pub job_dir: Option<PathBuf>,
+    //in seconds.
+    #[arg(
+        long = "poll_deadline",
+        env = "EIGEN_GEVULOT_POLL_DEADLINE",
+        global = true
+    )]
+    pub poll_deadline: Option<u64>,
+    //in seconds.
+    #[arg(
+        long = "download_timeout",
+        env = "EIGEN_GEVULOT_DOWNLOAD_TIMEOUT",
+        global = true
+    )]
+    pub download_timeout: Option<u64>,
+}
+
+impl ConfigArgs {
+    /// The selected profile of the config file, or the defaults if there's no config file, with
+    /// the overrides on top.
+    pub fn load(&self) -> Result<ClientConfig> {
+        let path = self
+            .config
+            .clone()
+            .or_else(|| Some(PathBuf::from(DEFAULT_CONFIG_FILE)).filter(|path| path.is_file()));
+        let mut config = match (&path, &self.profile) {
+            (Some(path), profile) => ConfigFile::load(path)?.profile(profile.as_deref())?,
+            (None, Some(profile)) => bail!(
+                "the profile `{}` is given, but {} doesn't exist",
+                profile,
+                DEFAULT_CONFIG_FILE
+            ),
+            (None, None) => ClientConfig::default(),
+        };
+        self.apply(&mut config);
+        Ok(config)
+    }
+
+    pub fn apply(&self, config: &mut ClientConfig) {
+        if let Some(url) = &self.json_rpc_url {
+            config.json_rpc_url = url.clone();
+        }
+        if let Some(key_file) = &self.key_file {
+            config.key_file = key_file.clone();
+        }
+        if self.rpc_timeout.is_some() {
+            config.rpc_timeout = self.rpc_timeout;
+        }
+        if let Some(url) = &self.http_url {
+            config.http_url = url.clone();
+        }
+        if let Some(path) = &self.http_server_work_path {
+            config.http_server_work_path = path.clone();
+        }
+        if self.serve.is_some() {
+            config.serve = self.serve;
+        }
+        if let Some(hash) = &self.prover_hash {
+            config.prover_hash = Some(hash.clone());
+            for task in config.tasks.values_mut() {
+                task.prover_hash = Some(hash.clone());
+            }
+        }
+        if let Some(hash) = &self.verifier_hash {
+            config.verifier_hash = Some(hash.clone());
+            for task in config.tasks.values_mut() {
+                task.verifier_hash = Some(hash.clone());
+            }
+        }
+        if let Some(out_dir) = &self.out_dir {
+            config.out_dir = out_dir.clone();
+        }
+        if let Some(job_dir) = &self.job_dir {
+            config.job_dir = job_dir.clone();
+        }
+        if let Some(deadline) = self.poll_deadline {
+            config.poll_deadline = deadline;
+        }
+        if let Some(timeout) = self.download_timeout {
+            config.download_timeout = timeout;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+default_profile = "devnet"
+
+[profiles.local]
+json_rpc_url = "http://127.0.0.1:9944"
+serve = "127.0.0.1:8080"
+
+[profiles.devnet]
+json_rpc_url = "http://api.devnet.gevulot.com:9944"
+prover_hash = "a0"
+verifier_hash = "b0"
+
+[profiles.devnet.tasks.lr]
+prover_hash = "a1"
+
+[profiles.devnet.tasks.fib]
+verifier_hash = "b2"
+"#;
+
+    fn config_file() -> ConfigFile {
+        toml::from_str(CONFIG).unwrap()
+    }
+
+    #[test]
+    fn default_profile() {
+        let config = config_file().profile(None).unwrap();
+        assert_eq!(config.profile.as_deref(), Some("devnet"));
+        assert_eq!(config.json_rpc_url, "http://api.devnet.gevulot.com:9944");
+        // the fields missing in the profile are the defaults
+        assert_eq!(config.http_url, ClientConfig::default().http_url);
+
+        let config = config_file().profile(Some("local")).unwrap();
+        assert_eq!(config.profile.as_deref(), Some("local"));
+        assert_eq!(config.serve, Some("127.0.0.1:8080".parse().unwrap()));
+
+        let config = ConfigFile::default().profile(None).unwrap();
+        assert_eq!(config.profile, None);
+        assert_eq!(config.json_rpc_url, ClientConfig::default().json_rpc_url);
+    }
+
+    #[test]
+    fn unknown_profile() {
+        let err = config_file().profile(Some("mainnet")).unwrap_err();
+        assert!(
+            err.to_string().contains("unknown profile `mainnet`"),
+            "{}",
+            err
+        );
+        assert!(err.to_string().contains("devnet"), "{}", err);
+
+        let file = ConfigFile {
+            default_profile: Some("mainnet".to_string()),
+            ..config_file()
+        };
+        assert!(file.profile(None).is_err());
+    }
+
+    #[test]
+    fn unknown_field() {
+        let err =
+            toml::from_str::<ConfigFile>("[profiles.local]\njson_rpc_uri = \"x\"\n").unwrap_err();
+        assert!(err.to_string().contains("json_rpc_uri"), "{}", err);
+    }
+
+    #[test]
+    fn task_programs() {
+        let config = config_file().profile(None).unwrap();
+        let lr = config.programs("lr");
+        assert_eq!(lr.prover_hash.as_deref(), Some("a1"));
+        assert_eq!(lr.verifier_hash.as_deref(), Some("b0"));
+        let fib = config.programs("fib");
+        assert_eq!(fib.prover_hash.as_deref(), Some("a0"));
+        assert_eq!(fib.verifier_hash.as_deref(), Some("b2"));
+        assert_eq!(config.programs("other").prover_hash.as_deref(), Some("a0"));
+    }
+
+    #[test]
+    fn args_override_the_task_programs() {
+        let mut config = config_file().profile(None).unwrap();
+        let args = ConfigArgs {
+            prover_hash: Some("a9".to_string()),
+            poll_deadline: Some(60),
+            ..Default::default()
+        };
+        args.apply(&mut config);
+        for task in ["lr", "fib", "other"] {
+            assert_eq!(config.programs(task).prover_hash.as_deref(), Some("a9"));
+        }
+        // the verifiers of the profile are kept
+        assert_eq!(config.programs("fib").verifier_hash.as_deref(), Some("b2"));
+        assert_eq!(config.programs("lr").verifier_hash.as_deref(), Some("b0"));
+        assert_eq!(config.poll_deadline, 60);
+        assert_eq!(config.json_rpc_url, "http://api.devnet.gevulot.com:9944");
+
+        let args = ConfigArgs {
+            verifier_hash: Some("b9".to_string()),
+            ..Default::default()
+        };
+        args.apply(&mut config);
+        for task in ["lr", "fib", "other"] {
+            assert_eq!(config.programs(task).verifier_hash.as_deref(), Some("b9"));
+        }
+    }
+}
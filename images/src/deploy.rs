@@ -0,0 +1,162 @@
+//! Deploys the prover and verifier images, replacing `pack.sh` / `deploy.sh`.
+//!
+//! The deployment is described by a JSON file:
+//!
+//! ```json
+//! {
+//!   "name": "prover & verifier",
+//!   "publish_dir": "/data/http/",
+//!   "prover": {
+//!     "name": "#eigen-gevulot-prover",
+//!     "image": "~/.ops/images/gevulot-prover",
+//!     "image_url": "http://4.145.88.10:8080/gevulot-prover",
+//!     "cpus": 32,
+//!     "mem": 302144,
+//!     "gpus": 0
+//!   },
+//!   "verifier": { ... }
+//! }
+//! ```
+use anyhow::{anyhow, bail, Context, Result};
+use gevulot_node::types::program::ResourceRequest;
+use gevulot_node::types::transaction::{Payload, ProgramMetadata};
+use gevulot_node::types::{Hash, Transaction};
+use libsecp256k1::SecretKey;
+use serde::{Deserialize, Serialize};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::backend::ProvingBackend;
+use crate::file::{file_checksum, send_transaction};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProgramConfig {
+    pub name: String,
+    /// The image built by `ops build`, a leading `~/` is the home directory.
+    pub image: PathBuf,
+    /// The URL the node downloads the image from.
+    pub image_url: String,
+    pub cpus: u64,
+    /// In MB.
+    pub mem: u64,
+    #[serde(default)]
+    pub gpus: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeployConfig {
+    pub name: String,
+    /// The work path of the http file server, the images are copied there before the deployment.
+    #[serde(default)]
+    pub publish_dir: Option<PathBuf>,
+    pub prover: ProgramConfig,
+    pub verifier: ProgramConfig,
+}
+
+impl DeployConfig {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path).with_context(|| format!("read {:?}", path))?;
+        serde_json::from_str(&content).with_context(|| format!("parse {:?}", path))
+    }
+}
+
+/// The program hashes to use in the proving requests.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Deployment {
+    pub prover_hash: String,
+    pub verifier_hash: String,
+    pub tx_hash: String,
+    pub prover_image_checksum: String,
+    pub verifier_image_checksum: String,
+}
+
+/// Publishes the images, computes their checksums and sends the Deploy transaction.
+pub async fn deploy(
+    client: &impl ProvingBackend,
+    key: &SecretKey,
+    config: &DeployConfig,
+) -> Result<Deployment> {
+    let prover = program_metadata(&config.prover, config.publish_dir.as_deref()).await?;
+    let verifier = program_metadata(&config.verifier, config.publish_dir.as_deref()).await?;
+    log::info!(
+        "Deploy the prover {} and the verifier {}",
+        prover.hash,
+        verifier.hash
+    );
+
+    let prover_hash = prover.hash.to_string();
+    let verifier_hash = verifier.hash.to_string();
+    let prover_image_checksum = prover.image_file_checksum.clone();
+    let verifier_image_checksum = verifier.image_file_checksum.clone();
+    let tx = Transaction::new(
+        Payload::Deploy {
+            name: config.name.clone(),
+            prover,
+            verifier,
+        },
+        key,
+    );
+    let tx_hash = send_transaction(client, &tx)
+        .await
+        .map_err(|err| anyhow!("deploy {}: {}", config.name, err))?;
+    Ok(Deployment {
+        prover_hash,
+        verifier_hash,
+        tx_hash: tx_hash.to_string(),
+        prover_image_checksum,
+        verifier_image_checksum,
+    })
+}
+
+async fn program_metadata(
+    program: &ProgramConfig,
+    publish_dir: Option<&Path>,
+) -> Result<ProgramMetadata> {
+    let image = expand_home(&program.image);
+    if !image.is_file() {
+        bail!(
+            "the image {:?} of {} doesn't exist, build it with `ops build`",
+            image,
+            program.name
+        );
+    }
+    if let Some(dir) = publish_dir {
+        let file_name = image
+            .file_name()
+            .ok_or_else(|| anyhow!("no file name in {:?}", image))?;
+        fs::create_dir_all(dir).with_context(|| format!("create {:?}", dir))?;
+        let dest = dir.join(file_name);
+        fs::copy(&image, &dest).with_context(|| format!("copy {:?} to {:?}", image, dest))?;
+        log::info!("Published {:?} to {:?}", image, dest);
+    }
+
+    let image_str = image
+        .to_str()
+        .ok_or_else(|| anyhow!("the path {:?} isn't valid UTF-8", image))?;
+    let checksum = file_checksum(image_str)
+        .await
+        .map_err(|err| anyhow!("{}", err))?;
+    log::info!("The checksum of {:?} is {}", image, checksum);
+
+    let mut metadata = ProgramMetadata {
+        name: program.name.clone(),
+        hash: Hash::default(),
+        image_file_url: program.image_url.clone(),
+        image_file_checksum: checksum,
+        resource_requirements: Some(ResourceRequest {
+            mem: program.mem,
+            cpus: program.cpus,
+            gpus: program.gpus,
+        }),
+    };
+    metadata.update_hash();
+    Ok(metadata)
+}
+
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), std::env::var_os("HOME")) {
+        (Ok(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => path.to_path_buf(),
+    }
+}
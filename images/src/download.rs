@@ -0,0 +1,282 @@
+//! Streaming downloads of the result files, resumed with HTTP Range and retried with backoff.
+use anyhow::{anyhow, bail, Context, Result};
+use reqwest::{header, StatusCode};
+use tokio::fs::{self, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Semaphore;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, timeout};
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::file::file_checksum;
+
+#[derive(Debug, Clone)]
+pub struct DownloadOptions {
+    pub attempts: u32,
+    pub connect_timeout: Duration,
+    /// The longest wait for the next chunk of the response body.
+    pub chunk_timeout: Duration,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// The number of files downloaded at the same time.
+    pub concurrency: usize,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        DownloadOptions {
+            attempts: 5,
+            connect_timeout: Duration::from_secs(30),
+            chunk_timeout: Duration::from_secs(60),
+            initial_backoff: Duration::from_secs(2),
+            max_backoff: Duration::from_secs(60),
+            concurrency: 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DownloadRequest {
+    pub url: String,
+    pub dest: PathBuf,
+    /// The Gevulot checksum of the file, checked before the file is renamed into place.
+    pub checksum: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct DownloadManager {
+    client: reqwest::Client,
+    options: DownloadOptions,
+}
+
+impl DownloadManager {
+    pub fn new(options: DownloadOptions) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .connect_timeout(options.connect_timeout)
+            .build()?;
+        Ok(DownloadManager { client, options })
+    }
+
+    /// Downloads all the files, at most `concurrency` at the same time. The results are in the
+    /// order of the requests, a download task which panicked is the error of its request.
+    pub async fn download_all(&self, requests: Vec<DownloadRequest>) -> Vec<Result<PathBuf>> {
+        let semaphore = Arc::new(Semaphore::new(self.options.concurrency.max(1)));
+        // the handles are in the order of the requests, so a failed task keeps its index
+        let tasks: Vec<JoinHandle<Result<PathBuf>>> = requests
+            .into_iter()
+            .map(|request| {
+                let manager = self.clone();
+                let semaphore = semaphore.clone();
+                tokio::spawn(async move {
+                    let _permit = semaphore.acquire_owned().await;
+                    manager.download(&request).await
+                })
+            })
+            .collect();
+
+        let mut results = Vec::with_capacity(tasks.len());
+        for task in tasks {
+            results.push(match task.await {
+                Ok(result) => result,
+                Err(e) => Err(anyhow!("download task error: {}", e)),
+            });
+        }
+        results
+    }
+
+    pub async fn download(&self, request: &DownloadRequest) -> Result<PathBuf> {
+        if let Some(dir) = request.dest.parent() {
+            fs::create_dir_all(dir)
+                .await
+                .with_context(|| format!("create {:?}", dir))?;
+        }
+        let part = part_path(&request.dest);
+
+        let attempts = self.options.attempts.max(1);
+        let mut last_error = anyhow!("no download attempt");
+        for attempt in 1..=attempts {
+            match self.try_download(request, &part).await {
+                Ok(()) => {
+                    fs::rename(&part, &request.dest).await?;
+                    log::info!("Downloaded {} to {:?}", request.url, request.dest);
+                    return Ok(request.dest.clone());
+                }
+                Err(e) => {
+                    log::warn!(
+                        "Attempt {}/{} to download {}: {:#}",
+                        attempt,
+                        attempts,
+                        request.url,
+                        e
+                    );
+                    last_error = e;
+                }
+            }
+            if attempt < attempts {
+                sleep(self.backoff(attempt)).await;
+            }
+        }
+        let _ = fs::remove_file(&part).await;
+        Err(last_error.context(format!("download {}", request.url)))
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.options
+            .initial_backoff
+            .saturating_mul(1u32 << attempt.saturating_sub(1).min(16))
+            .min(self.options.max_backoff)
+    }
+
+    // Appends to the partial file from where the previous attempt stopped. Without a checksum a
+    // resumed file couldn't be checked, so it's downloaded from the start.
+    async fn try_download(&self, request: &DownloadRequest, part: &Path) -> Result<()> {
+        let offset = match fs::metadata(part).await {
+            Ok(m) if request.checksum.is_some() => m.len(),
+            _ => 0,
+        };
+
+        let mut builder = self.client.get(&request.url);
+        if offset > 0 {
+            builder = builder.header(header::RANGE, format!("bytes={}-", offset));
+        }
+        let mut response = builder.send().await?;
+
+        let append = match response.status() {
+            StatusCode::PARTIAL_CONTENT => {
+                let start = response
+                    .headers()
+                    .get(header::CONTENT_RANGE)
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(content_range_start);
+                if start != Some(offset) {
+                    let _ = fs::remove_file(part).await;
+                    bail!(
+                        "requested the range from {}, got {:?}, start over",
+                        offset,
+                        response.headers().get(header::CONTENT_RANGE)
+                    );
+                }
+                true
+            }
+            // the partial file doesn't match the file on the server anymore
+            StatusCode::RANGE_NOT_SATISFIABLE => {
+                let _ = fs::remove_file(part).await;
+                bail!("the range from {} isn't satisfiable, start over", offset);
+            }
+            status if status.is_success() => false,
+            status => bail!("HTTP status {}", status),
+        };
+
+        let mut dest = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(part)
+            .await
+            .with_context(|| format!("open {:?}", part))?;
+        loop {
+            let chunk = timeout(self.options.chunk_timeout, response.chunk())
+                .await
+                .map_err(|_| anyhow!("no data for {:?}", self.options.chunk_timeout))??;
+            match chunk {
+                Some(bytes) => dest.write_all(&bytes).await?,
+                None => break,
+            }
+        }
+        dest.flush().await?;
+        drop(dest);
+
+        self.check(request, part).await
+    }
+
+    async fn check(&self, request: &DownloadRequest, part: &Path) -> Result<()> {
+        let Some(expected) = &request.checksum else {
+            return Ok(());
+        };
+        let actual = file_checksum(&part.to_string_lossy())
+            .await
+            .map_err(|e| anyhow!("{}", e))?;
+        if !actual.eq_ignore_ascii_case(expected) {
+            // the partial file is corrupted, start over on the next attempt
+            let _ = fs::remove_file(part).await;
+            bail!("checksum mismatch: expected {}, got {}", expected, actual);
+        }
+        Ok(())
+    }
+}
+
+// The first byte of `Content-Range: bytes <start>-<end>/<size>`.
+fn content_range_start(value: &str) -> Option<u64> {
+    let (start, _) = value.trim().strip_prefix("bytes ")?.split_once('-')?;
+    start.trim().parse().ok()
+}
+
+fn part_path(dest: &Path) -> PathBuf {
+    let mut name = dest.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    dest.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(name: &str, content: &[u8]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("images-download-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn parse_content_range() {
+        assert_eq!(content_range_start("bytes 100-199/1000"), Some(100));
+        assert_eq!(content_range_start(" bytes 0-0/1 "), Some(0));
+        assert_eq!(content_range_start("bytes 100-199/*"), Some(100));
+        assert_eq!(content_range_start("bytes */1000"), None);
+        assert_eq!(content_range_start("items 100-199/1000"), None);
+        assert_eq!(content_range_start("bytes -5-199/1000"), None);
+    }
+
+    #[test]
+    fn part_next_to_the_file() {
+        assert_eq!(
+            part_path(Path::new("/data/out/lr_proof.bin")),
+            PathBuf::from("/data/out/lr_proof.bin.part")
+        );
+    }
+
+    #[tokio::test]
+    async fn checksum_mismatch_removes_the_part() {
+        let manager = DownloadManager::new(DownloadOptions::default()).unwrap();
+        let part = temp_file("mismatch.bin.part", b"corrupted");
+        let request = DownloadRequest {
+            url: "http://127.0.0.1:9995/mismatch.bin".to_string(),
+            dest: part.with_file_name("mismatch.bin"),
+            checksum: Some("0".repeat(64)),
+        };
+        let err = manager.check(&request, &part).await.unwrap_err();
+        assert!(err.to_string().contains("checksum mismatch"), "{:#}", err);
+        assert!(!part.exists());
+    }
+
+    #[tokio::test]
+    async fn matching_checksum_keeps_the_part() {
+        let manager = DownloadManager::new(DownloadOptions::default()).unwrap();
+        let part = temp_file("match.bin.part", b"proof");
+        let checksum = file_checksum(&part.to_string_lossy()).await.unwrap();
+        let request = DownloadRequest {
+            url: "http://127.0.0.1:9995/match.bin".to_string(),
+            dest: part.with_file_name("match.bin"),
+            checksum: Some(checksum.to_uppercase()),
+        };
+        manager.check(&request, &part).await.unwrap();
+        assert!(part.exists());
+        std::fs::remove_file(part).unwrap();
+    }
+}
@@ -1,7 +1,5 @@
 
-//use crate::server;
 use gevulot_node::{
-    rpc_client::{RpcClient,RpcClientBuilder,},
     types::{
         transaction::{Payload, ProgramData, Workflow, WorkflowStep},
         Transaction,TransactionTree,
@@ -13,156 +11,42 @@ use gevulot_node::{
 use gevulot_node::types::transaction::Created;
 
 use gevulot_node::types::Hash;
-use gevulot_cli::{calculate_hash_command,keyfile};
+use gevulot_cli::calculate_hash_command;
+use libsecp256k1::SecretKey;
+
+use crate::backend::ProvingBackend;
+use crate::client::ProvingRequest;
+use crate::download::{DownloadManager, DownloadOptions, DownloadRequest};
+use crate::manifest::ChunkFiles;
+use crate::payload::{PayloadError, TxOutput, TxPayload, VerificationPayload};
+use crate::poll::{PollError, PollPolicy};
+use crate::stage::StagedInputs;
+use crate::status::TreeNode;
 
 use std::{
     rc::Rc,
     path::PathBuf,
-    fs::File,
-    io:: Write,
-    time::Duration,
 };
 
-use tokio::time::sleep;
+use tokio::time::{sleep, Instant};
 
 use std::path::Path;
-use std::ffi::OsStr;
-
-use serde::{Deserialize, Serialize};
-
-
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Verification {
-    pub parent: String,
-    pub verifier: String,
-    pub verification: String,
-    pub files: Vec<FileJsn>,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-pub  struct FileJsn {
-    pub url: String,
-    pub checksum: String,
-    pub vm_path: String,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-pub  struct PayloadJsn {
-    pub Verification: Verification,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-pub  struct Root {
-    pub author: String,
-    pub hash: String,
-    pub payload: PayloadJsn,
-    pub nonce: u64,
-    pub signature: String,
-}
 
 type BoxResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
-pub async fn run_prover(
-    json_rpc_url: &String,
-    keyfile: &PathBuf,
-    prove_program_hsh: &String,
-    verify_program_hsh: &String,
-    trace_file: &String,
-    bi_file: &String,
-    asm_file: &String,
-    task_name: &String,
-    chunk_id : &String,
-    http_server_work_path:&String,
-    local_http_url: &String,
-    proof_file_out_path:  &String,
-    rpc_timeout: Option<u64>
-) -> BoxResult<()> {
-    
-
-    let mut client_builder = RpcClientBuilder::default();
-    if let Some(rpc_tm_out) = rpc_timeout {
-        client_builder = client_builder.request_timeout(Duration::from_secs(rpc_tm_out));
-    }
-    let client = client_builder
-        .build(json_rpc_url.to_owned())
-        .expect("build rpc client");
-
-    let tx_hash = call_rpc_prover(&client, &keyfile, &prove_program_hsh, &verify_program_hsh , &trace_file, &bi_file,
-        &asm_file,
-        &task_name,
-        &chunk_id,
-        &http_server_work_path,
-        &local_http_url).await?; 
-
-    ///////////
-    let wait_time :u64;
-    if  *task_name == "lr".to_string(){
-        wait_time =300 ;//second
-    }else{
-        wait_time =1100 ;
-    }
-    let leaf_hash = get_leaf_hash(&client, &tx_hash, wait_time).await.expect("Option<Hash> does not have a value");
-
-    let json_content = get_leaf_cnt(&client,leaf_hash).await?;
-    
-    log::info!("Leaf content: ");
-    let parsed: Root = serde_json::from_str(&json_content)?;
-   
-    //Download the result files from Gevulot server
-    for file in parsed.payload.Verification.files {
-        log::info!("File URL: {}", file.url);
-        log::info!("VM Path: {}", file.vm_path);
-        
-        //let path_str = "/work/test.log";
-        let path = Path::new(&file.vm_path);
-    
-        let file_name_os_str = path.file_name().expect("Path should have a file name");
-    
-        let file_name = OsStr::to_string_lossy(file_name_os_str).to_string();
-        let  file_path = format!("{}{}",&proof_file_out_path, file_name);
-
-       let _ = download_file(&file.url, &file_path).await.expect("Failed to download file");
-    }
-    //////////
-
-    Ok(())
-}
-
-pub async fn call_rpc_prover(client: &RpcClient,
-    keyfile: &PathBuf,
-    prove_program_hsh: &String,
-    verify_program_hsh: &String,
-    trace_file: &String,
-    bi_file: &String,
-    asm_file: &String,
-    task_name: &String,
-    chunk_id : &String,
-    http_server_work_path:&String,
-    local_http_url: &String)-> BoxResult<(Hash)>{
-
-    let key = keyfile::read_key_file(&keyfile).map_err(|err| {
-            format!(
-                "Error during key file:{} reading:{err}",
-                keyfile.to_str().unwrap_or("")
-            )
-        })?;
-
-    //the http_server_work_path is set during the installation of the http file server.
-    let  trace_file_hsh = file_hash(&trace_file, &http_server_work_path).await?;
-    let  bi_file_hsh = file_hash(&bi_file, &http_server_work_path).await?;
-    let  asm_file_hsh = file_hash(&asm_file, &http_server_work_path).await?;
-
-   
-    let trace_file_url = format!("{}/{}", local_http_url, trace_file);
-    let bi_file_url = format!("{}/{}", local_http_url, bi_file);
-    let ams_file_url = format!("{}/{}", local_http_url, asm_file);
-
+/// Sends the workflow of the request with its staged input files: the prover step and the
+/// verifier step of its outputs.
+pub async fn call_rpc_prover(client: &impl ProvingBackend, key: &SecretKey, request: &ProvingRequest, inputs: &StagedInputs) -> BoxResult<Hash> {
+    let StagedInputs { trace, bi, asm } = inputs;
+    let trace_file = &trace.file_name;
+    let bi_file = &bi.file_name;
+    let asm_file = &asm.file_name;
 
     let mut steps = vec![];
-    let prove_prg :Hash = (&(hex::decode(prove_program_hsh).map_err(|err| format!("program decoding hash error:{err}"))?)[..]).into();
-    let verify_prg :Hash  = (&(hex::decode(verify_program_hsh).map_err(|err| format!("program decoding hash error:{err}"))?)[..]).into();
+    let prove_prg = request.prover_hash;
+    let verify_prg = request.verifier_hash;
   
-    let step_prove = WorkflowStep {
+    let mut step_prove = WorkflowStep {
                              program: prove_prg.to_owned(),
                                     
                              args: vec![
@@ -173,54 +57,67 @@ pub async fn call_rpc_prover(client: &RpcClient,
                                 "--asm_file".to_string(),
                                 "/workspace/".to_string() + asm_file,
                                 "--task_name".to_string(),
-                                task_name.to_owned(),
+                                request.task_name.to_owned(),
                                 "--chunk_id".to_string(),
-                                chunk_id.to_owned(),
+                                request.chunk_id.to_string(),
                                 ],
                             inputs:vec![
                                 ProgramData::Input{
-                                    checksum: trace_file_hsh,
+                                    checksum: trace.checksum.to_owned(),
                                     file_name: "/workspace/".to_string() + trace_file,
-                                    file_url: trace_file_url.to_owned(),
+                                    file_url: trace.url.to_string(),
                                 },
                 
                                 ProgramData::Input{
-                                    checksum: bi_file_hsh,
+                                    checksum: bi.checksum.to_owned(),
                                     file_name: "/workspace/".to_string() + bi_file,
-                                    file_url: bi_file_url.to_owned(),
+                                    file_url: bi.url.to_string(),
                                 },
                             
                                 ProgramData::Input{
-                                    checksum: asm_file_hsh,
+                                    checksum: asm.checksum.to_owned(),
                                     file_name: "/workspace/".to_string() + asm_file,
-                                    file_url: ams_file_url.to_owned(),
+                                    file_url: asm.url.to_string(),
                                 },
                             ],
                     };
+    //the circom options overriding the config of the prover
+    step_prove.args.extend(request.circom.args());
 
 
-    let circom_file = format!("/workspace/{}_chunk_{}.circom", &task_name, &chunk_id);
-    let proof_file = format!("/workspace/{}_chunk_{}/{}_proof.bin", &task_name, &chunk_id, &task_name);
+    let chunk_files = ChunkFiles::new("/workspace", &request.task_name, request.chunk_id);
 
     let step_verify = WorkflowStep {
                     program: verify_prg,
 
                     args: vec![
                     "--circom_file".to_string(),
-                    circom_file.to_owned(),
+                    chunk_files.circom.to_owned(),
                     "--proof_file".to_string(),
-                    proof_file.to_owned(),   
+                    chunk_files.proof.to_owned(),   
+                    "--zkin_file".to_string(),
+                    chunk_files.zkin.to_owned(),
+                    "--manifest_file".to_string(),
+                    chunk_files.manifest.to_owned(),
                     ],
                     
                     inputs:vec![
                         ProgramData::Output {
                             source_program: prove_prg.to_owned(),
-                            file_name: circom_file.to_owned(),
+                            file_name: chunk_files.circom.to_owned(),
                         },
                         ProgramData::Output {
                             source_program:  prove_prg.to_owned(),
-                            file_name: proof_file.to_owned(),   
+                            file_name: chunk_files.proof.to_owned(),   
                         },     
+                        ProgramData::Output {
+                            source_program:  prove_prg.to_owned(),
+                            file_name: chunk_files.zkin.to_owned(),
+                        },
+                        ProgramData::Output {
+                            source_program:  prove_prg.to_owned(),
+                            file_name: chunk_files.manifest.to_owned(),
+                        },
                         //test.log
                         ProgramData::Output {
                             source_program:  prove_prg.to_owned(),
@@ -236,34 +133,45 @@ pub async fn call_rpc_prover(client: &RpcClient,
         Payload::Run {
             workflow: Workflow { steps },
         },
-        &key,
+        key,
     );
 
-    let tx_hash = send_transaction(&client, &tx).await?;
+    let tx_hash = send_transaction(client, &tx).await?;
     
     Ok(tx_hash)
 }
 
-// Asynchronous function to download a file from a URL and save it to a specified directory.
+/// Streams a file from a URL to `path`, see `DownloadManager` for the retries and resumption.
 pub async fn download_file(url: &str, path: &str) -> BoxResult<()> {
-    // Send a GET request to the specified URL.
-    let mut response = reqwest::get(url).await?;
-
-    // Ensure the request was successful.
-    //response.error_for_status()?.error_for_status()?; // Call twice to ensure a 200 OK status.
-
-    response = response.error_for_status()?;
-    // Write the content of the response body to a file.
-    let mut dest = File::create( path)?;
-    let content = response.bytes().await?;
-    dest.write_all(&content)?;
-    dest.flush()?; // Ensure all data is written to the file.
+    let manager = DownloadManager::new(DownloadOptions::default())?;
+    manager.download(&DownloadRequest {
+        url: url.to_string(),
+        dest: PathBuf::from(path),
+        checksum: None,
+    }).await?;
+    Ok(())
+}
 
+/// Downloads to `<path>.part`, checks the Gevulot checksum of the file and renames it to `path`.
+/// A download with a wrong checksum is removed and retried up to `attempts` times.
+pub async fn download_verified_file(url: &str, path: &str, checksum: &str, attempts: u32) -> BoxResult<()> {
+    let manager = DownloadManager::new(DownloadOptions { attempts, ..Default::default() })?;
+    manager.download(&DownloadRequest {
+        url: url.to_string(),
+        dest: PathBuf::from(path),
+        checksum: Some(checksum.to_string()),
+    }).await?;
     Ok(())
 }
 
+/// The checksum of a file, computed the way the Gevulot node does (`gevulot-cli calculate-hash`).
+pub async fn file_checksum(path: &str) -> BoxResult<String> {
+    calculate_hash_command(&PathBuf::from(path))
+        .await
+        .map_err(|err| format!("calculate the hash of {path} error:{err}").into())
+}
 
-pub async fn send_transaction(client: &RpcClient, tx: &Transaction<Created>) -> std::result::Result<Hash, String> {
+pub async fn send_transaction(client: &impl ProvingBackend, tx: &Transaction<Created>) -> std::result::Result<Hash, String> {
     client
         .send_transaction(tx)
         .await
@@ -285,71 +193,147 @@ pub async fn send_transaction(client: &RpcClient, tx: &Transaction<Created>) ->
 }
 
 
-pub async fn get_leaf_hash(client: &RpcClient, hash: &Hash, waiting_time: u64)->Option<Hash>{
-    //wait a few minutes for the proving task to finish
+/// Waits until a leaf of the transaction tree of `hash` is a verification of `verifier_hash`, or
+/// of any verifier if none is given. The proof of a workflow is a leaf until its verification
+/// arrives, so a leaf alone isn't enough.
+pub async fn wait_for_tx_tree(client: &impl ProvingBackend, hash: &Hash, verifier_hash: Option<&str>, policy: &PollPolicy) -> Result<TransactionTree, PollError> {
+    let (tx_tree, _) = wait_for_verification(client, hash, verifier_hash, policy).await?;
+    Ok(tx_tree)
+}
+
+// Polls the tree until it has a verification leaf of the verifier, returns the tree and the first
+// such leaf.
+async fn wait_for_verification(client: &impl ProvingBackend, hash: &Hash, verifier_hash: Option<&str>, policy: &PollPolicy) -> Result<(TransactionTree, Hash), PollError> {
+    //wait for the proving task to finish
     log::info!("waiting the proving task to finish");
-    sleep(Duration::from_secs(waiting_time)).await;
-    let mut counter = 0;
-
-    loop{
-        match client.get_tx_tree(&hash).await {
-                Ok(tx_tree) =>{
-                    print_tx_tree(&tx_tree, 0);
-                    log::info!("get_leaf:");
-                    let tree_root = Rc::new(tx_tree);
-                    let first_leaf = find_first_leaf(&tree_root);
-                    match first_leaf {
-                        Some(leaf) => {
-                            match leaf.as_ref() {
-                                TransactionTree::Leaf { hash, .. } => {
-                                    log::info!("The hash of the first leaf is: {}", hash);
-                                    
-                                    return Some(*hash);
-                                },
-                                // do nothing
-                                _ => {}
-                            }
-                        },
-                        None => {
-                            log::info!("==== No leaf node found. =======");
-                            //break;
-                            return None;
-                        }
-                    }
+    let start = Instant::now();
+    sleep(policy.initial_delay).await;
+    let mut attempts = 0;
+    let mut tree_found = false;
+    let mut last_error = String::new();
+
+    loop {
+        attempts += 1;
+        match client.get_tx_tree(hash).await {
+            Ok(tx_tree) => {
+                tree_found = true;
+                log::debug!("The transaction tree:\n{}", TreeNode::from(&tx_tree));
+                if let Some(leaf) = first_verification_leaf(client, hash, &tx_tree, verifier_hash).await? {
+                    return Ok((tx_tree, leaf));
+                }
+                log::info!("No verification leaf found, the verification isn't finished");
+            }
+            Err(err) => {
+                log::info!("The task is executing or there is an error:{}", err);
+                last_error = err.to_string();
+            }
+        };
 
-                } ,
-                Err(err) =>{
-                    //println!("An error while fetching transaction tree: {err}");
-                    log::info!("The task is executing or there is an error:{}",err);
+        let elapsed = start.elapsed();
+        if elapsed >= policy.deadline {
+            let tx_hash = hash.to_string();
+            return Err(if tree_found {
+                PollError::Timeout { tx_hash, elapsed, attempts }
+            } else {
+                PollError::NotFound { tx_hash, elapsed, attempts, last_error }
+            });
+        }
 
-                },
-        };
+        let delay = policy.interval(attempts).min(policy.deadline - elapsed);
+        log::info!("Try {} times, but not get the leaf, waiting {:?}... ", attempts, delay);
+        sleep(delay).await;
+    }
+}
 
-        counter +=1;
-        if counter >50 {//Maybe different proving task costs different time !!
-            //try 50 times, about 50*20s=1000s 
+pub async fn get_leaf_hash(client: &impl ProvingBackend, hash: &Hash, verifier_hash: Option<&str>, policy: &PollPolicy) -> Result<Hash, PollError> {
+    let (_, first_leaf) = wait_for_verification(client, hash, verifier_hash, policy).await?;
+    log::info!("The hash of the first leaf is: {}", first_leaf);
+    Ok(first_leaf)
+}
 
-            return None;
+// The first leaf carrying a verification of the verifier, the other leaves are skipped. A leaf
+// which can't be fetched or decoded fails the wait, like in `get_verification_leaves`.
+async fn first_verification_leaf(client: &impl ProvingBackend, tx_hash: &Hash, tree: &TransactionTree, verifier_hash: Option<&str>) -> Result<Option<Hash>, PollError> {
+    let of_verifier = |verifier: &str| verifier_hash.map_or(true, |h| verifier.eq_ignore_ascii_case(h));
+    for hash in collect_leaves(tree) {
+        match get_tx_output(client, hash).await {
+            Ok(output) => match &output.payload {
+                TxPayload::Verification(v) if of_verifier(&v.verifier) => return Ok(Some(hash)),
+                TxPayload::Verification(v) => log::debug!("The leaf {} is a verification of {}", hash, v.verifier),
+                payload => log::debug!("The leaf {} is a {} transaction", hash, payload.kind()),
+            },
+            Err(err) => {
+                return Err(PollError::Leaf {
+                    tx_hash: tx_hash.to_string(),
+                    leaf: hash.to_string(),
+                    error: err.to_string(),
+                })
+            }
         }
-         
-        log::info!("Try {} times, but not get tx tree, waiting... ",counter);
-        sleep(Duration::from_secs(20)).await;
-                 
     }
-    
+    Ok(None)
 }
 
+/// A verification leaf of the transaction tree.
+#[derive(Debug)]
+pub struct LeafResult {
+    pub hash: Hash,
+    pub verification: VerificationPayload,
+}
 
+/// Fetches every leaf of the tree carrying a verification payload. The other leaves, such as a
+/// proof whose verification hasn't arrived yet, are skipped with a warning.
+pub async fn get_verification_leaves(client: &impl ProvingBackend, tree: &TransactionTree) -> BoxResult<Vec<LeafResult>> {
+    let mut results = vec![];
+    for hash in collect_leaves(tree) {
+        match get_tx_output(client, hash).await?.into_verification() {
+            Ok(verification) => results.push(LeafResult { hash, verification }),
+            Err(PayloadError::UnexpectedVariant { found, .. }) => {
+                log::warn!("Skip the leaf {}, it is a {} transaction", hash, found);
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(results)
+}
 
-pub async fn file_hash(file:&String, http_server_work_path:&String)->BoxResult<String> {
-
-    let filename = format!("{}/{}",http_server_work_path, file);
-    let hsh_str= calculate_hash_command(&PathBuf::from(filename)).await.expect("get file hash");
+/// Chooses the leaf of the given verifier. When several leaves of the verifier return different
+/// files, the result returned by the most leaves wins.
+pub fn select_leaf(leaves: Vec<LeafResult>, verifier_hash: &str) -> BoxResult<LeafResult> {
+    let mut groups: Vec<(Vec<(String, String)>, Vec<LeafResult>)> = vec![];
+    for leaf in leaves {
+        if !leaf.verification.verifier.eq_ignore_ascii_case(verifier_hash) {
+            log::info!("Skip the leaf {} of the verifier {}", leaf.hash, leaf.verification.verifier);
+            continue;
+        }
+        let mut key: Vec<(String, String)> = leaf
+            .verification
+            .files
+            .iter()
+            .map(|f| (f.vm_path.clone(), f.checksum.clone()))
+            .collect();
+        key.sort();
+        match groups.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, group)) => group.push(leaf),
+            None => groups.push((key, vec![leaf])),
+        }
+    }
 
-    Ok(hsh_str)
+    if groups.len() > 1 {
+        log::warn!("The verifier {} returned {} different results", verifier_hash, groups.len());
+    }
+    // max_by_key returns the last maximum, so reverse to prefer the first result on a tie
+    groups
+        .into_iter()
+        .rev()
+        .max_by_key(|(_, group)| group.len())
+        .and_then(|(_, group)| group.into_iter().next())
+        .ok_or_else(|| format!("No verification leaf of the verifier {verifier_hash}").into())
 }
 
-pub async fn  get_tx_tree(client: &RpcClient, tx_hash: String)-> BoxResult<TransactionTree>{
+
+
+pub async fn  get_tx_tree(client: &impl ProvingBackend, tx_hash: String)-> BoxResult<TransactionTree>{
 
     let hash = Hash::from(tx_hash);
     let tx_tree =  client.get_tx_tree(&hash).await?;
@@ -357,33 +341,22 @@ pub async fn  get_tx_tree(client: &RpcClient, tx_hash: String)-> BoxResult<Trans
     Ok(tx_tree)
 }
 
-fn print_tx_tree(tree: &TransactionTree, indentation: u16) {
+/// Prints the tree as indented text, see `status::TreeNode` for the tree as data.
+pub fn print_tx_tree(tree: &TransactionTree, indentation: u16) {
+    print!("{}", TreeNode::from(tree).render(indentation as usize));
+}
+
+
+/// Every leaf hash of the tree, in depth-first order.
+pub fn collect_leaves(tree: &TransactionTree) -> Vec<Hash> {
     match tree {
-        TransactionTree::Root { children, hash } => {
-            println!("Root: {hash}");
-            children
-                .iter()
-                .for_each(|x| print_tx_tree(x, indentation + 1));
-        }
-        TransactionTree::Node { children, hash } => {
-            println!(
-                "{}Node: {hash}",
-                (0..indentation).map(|_| "\t").collect::<String>()
-            );
-            children
-                .iter()
-                .for_each(|x| print_tx_tree(x, indentation + 1));
-        }
-        TransactionTree::Leaf { hash } => {
-            println!(
-                "{}Leaf: {hash}",
-                (0..indentation).map(|_| "\t").collect::<String>()
-            );
+        TransactionTree::Leaf { hash } => vec![*hash],
+        TransactionTree::Root { children, .. } | TransactionTree::Node { children, .. } => {
+            children.iter().flat_map(|child| collect_leaves(child)).collect()
         }
     }
 }
 
-
 pub  fn find_first_leaf(tree: &Rc<TransactionTree>) -> Option<&Rc<TransactionTree>> {
         match &**tree {
             TransactionTree::Leaf { .. } => Some(tree),
@@ -399,13 +372,207 @@ pub  fn find_first_leaf(tree: &Rc<TransactionTree>) -> Option<&Rc<TransactionTre
     }
 
 
-pub async fn get_leaf_cnt(client: &RpcClient, hash: Hash)-> BoxResult<String>{
-    let output_json = client
-    .get_transaction(&hash)
-    .await
-    .and_then(|tx_output| serde_json::to_string(&tx_output).map_err(|err| err.into()))?;
-
-    Ok(output_json)
+pub async fn get_tx_output(client: &impl ProvingBackend, hash: Hash) -> BoxResult<TxOutput> {
+    client.get_transaction(&hash).await
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::fixtures::*;
+    use crate::backend::MemoryBackend;
+    use serde_json::json;
+    use std::time::Duration;
+
+    fn leaf(byte: u8, verifier: u8, checksum: u8) -> LeafResult {
+        LeafResult {
+            hash: hash(byte),
+            verification: verification_payload(&hash(1), &hash(verifier), &hash(checksum)),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn wait_without_tree_is_not_found() {
+        let backend = MemoryBackend::new();
+        let err = wait_for_tx_tree(&backend, &hash(1), None, &policy())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, PollError::NotFound { .. }), "{}", err);
+    }
 
+    #[tokio::test(start_paused = true)]
+    async fn wait_without_leaf_times_out() {
+        let backend = MemoryBackend::new();
+        set_tree(&backend, &hash(1), &[]);
+        let err = wait_for_tx_tree(&backend, &hash(1), None, &policy())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, PollError::Timeout { .. }), "{}", err);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn tree_without_verification_leaf() {
+        let backend = MemoryBackend::new();
+        set_tree(&backend, &hash(1), &[hash(2)]);
+        insert(&backend, &hash(2), proof(&hash(1)));
+
+        let err = wait_for_tx_tree(&backend, &hash(1), None, &policy())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, PollError::Timeout { .. }), "{}", err);
+
+        let tree = backend.get_tx_tree(&hash(1)).await.unwrap();
+        let leaves = get_verification_leaves(&backend, &tree).await.unwrap();
+        assert!(leaves.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn wait_skips_the_proof_leaf() {
+        let backend = MemoryBackend::new();
+        set_tree(&backend, &hash(1), &[hash(2)]);
+        insert(&backend, &hash(2), proof(&hash(1)));
+
+        let verified = async {
+            sleep(Duration::from_secs(45)).await;
+            let tree = TransactionTree::Root {
+                hash: hash(1),
+                children: vec![TransactionTree::Node {
+                    hash: hash(2),
+                    children: vec![TransactionTree::Leaf { hash: hash(3) }],
+                }],
+            };
+            insert(&backend, &hash(3), verification(&hash(2)));
+            backend.set_tree(&hash(1).to_string(), serde_json::to_value(&tree).unwrap());
+        };
+        let (tree, _) = tokio::join!(
+            wait_for_tx_tree(&backend, &hash(1), None, &policy()),
+            verified
+        );
+        let leaves = get_verification_leaves(&backend, &tree.unwrap())
+            .await
+            .unwrap();
+        assert_eq!(leaves.len(), 1);
+        assert_eq!(leaves[0].hash, hash(3));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn malformed_payload_is_a_payload_error() {
+        let backend = MemoryBackend::new();
+        let tx = json!({
+            "author": "",
+            "hash": hash(2).to_string(),
+            "payload": { "Verification": { "parent": 1 } },
+            "nonce": 0,
+            "signature": "",
+        });
+        backend.insert_tx(&hash(2).to_string(), tx);
+        set_tree(&backend, &hash(1), &[hash(2)]);
+
+        let err = get_tx_output(&backend, hash(2)).await.unwrap_err();
+        assert!(
+            matches!(
+                err.downcast_ref::<PayloadError>(),
+                Some(PayloadError::Decode { .. })
+            ),
+            "{}",
+            err
+        );
+        let tree = backend.get_tx_tree(&hash(1)).await.unwrap();
+        assert!(get_verification_leaves(&backend, &tree).await.is_err());
+
+        // the wait fails at once instead of timing out
+        let start = Instant::now();
+        let err = wait_for_tx_tree(&backend, &hash(1), None, &policy())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, PollError::Leaf { .. }), "{}", err);
+        assert!(start.elapsed() < policy().deadline);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn wait_for_the_verifier() {
+        let backend = MemoryBackend::new();
+        set_tree(&backend, &hash(1), &[hash(2)]);
+        insert(&backend, &hash(2), verification_by(&hash(1), &hash(0xb1)));
+
+        let verifier = hash(0xb0).to_string();
+        let err = wait_for_tx_tree(&backend, &hash(1), Some(&verifier), &policy())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, PollError::Timeout { .. }), "{}", err);
+        assert!(wait_for_tx_tree(&backend, &hash(1), None, &policy())
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn unavailable_node_recovers() {
+        let backend = MemoryBackend::new();
+        set_tree(&backend, &hash(1), &[hash(2)]);
+        insert(&backend, &hash(2), verification(&hash(1)));
+        backend.fail_next(3);
+
+        let tree = wait_for_tx_tree(&backend, &hash(1), None, &policy())
+            .await
+            .unwrap();
+        let leaves = get_verification_leaves(&backend, &tree).await.unwrap();
+        assert_eq!(leaves.len(), 1);
+        assert_eq!(leaves[0].hash, hash(2));
+    }
+
+    #[tokio::test]
+    async fn proof_output_is_not_a_verification() {
+        let backend = MemoryBackend::new();
+        insert(&backend, &hash(2), proof(&hash(1)));
+
+        let output = get_tx_output(&backend, hash(2)).await.unwrap();
+        assert_eq!(output.payload.kind(), "Proof");
+        let err = output.into_verification().unwrap_err();
+        assert!(
+            matches!(
+                err,
+                PayloadError::UnexpectedVariant {
+                    expected: "Verification",
+                    found: "Proof",
+                    ..
+                }
+            ),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn select_leaf_takes_the_majority() {
+        let verifier = hash(0xb0).to_string();
+        let leaves = vec![leaf(2, 0xb0, 0xc1), leaf(3, 0xb0, 0xc0), leaf(4, 0xb0, 0xc0)];
+        let selected = select_leaf(leaves, &verifier).unwrap();
+        assert_eq!(selected.hash, hash(3));
+    }
+
+    #[test]
+    fn select_leaf_prefers_the_first_on_a_tie() {
+        let verifier = hash(0xb0).to_string();
+        let leaves = vec![
+            leaf(2, 0xb0, 0xc1),
+            leaf(3, 0xb0, 0xc0),
+            leaf(4, 0xb0, 0xc0),
+            leaf(5, 0xb0, 0xc1),
+        ];
+        let selected = select_leaf(leaves, &verifier).unwrap();
+        assert_eq!(selected.hash, hash(2));
+    }
+
+    #[test]
+    fn select_leaf_filters_the_verifier() {
+        // the other verifier's leaves don't count towards the majority
+        let verifier = hash(0xb0).to_string().to_uppercase();
+        let leaves = vec![leaf(2, 0xb1, 0xc1), leaf(3, 0xb1, 0xc1), leaf(4, 0xb0, 0xc0)];
+        let selected = select_leaf(leaves, &verifier).unwrap();
+        assert_eq!(selected.hash, hash(4));
+
+        let err = select_leaf(vec![leaf(2, 0xb1, 0xc0)], &verifier).unwrap_err();
+        assert!(err.to_string().contains("No verification leaf"), "{}", err);
+        assert!(select_leaf(vec![], &verifier).is_err());
+    }
+}
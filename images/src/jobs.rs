@@ -0,0 +1,223 @@
+//! A local record of the submitted proving requests, so a job can be resumed after a restart.
+//!
+//! The store is a directory with one `<tx hash>.json` file per job, rewritten atomically on every
+//! state change. The default directory is `~/.eigen-gevulot/jobs`.
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::circom::CircomOverrides;
+use crate::client::ProvingRequest;
+use crate::stage::{StagedFile, StagedInputs};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    /// The Run transaction is sent, the results aren't downloaded yet.
+    Submitted,
+    /// The results are downloaded to `out_dir`.
+    Downloaded,
+    /// Waiting or downloading failed, the job can be resumed.
+    Failed,
+}
+
+impl JobState {
+    pub fn is_finished(&self) -> bool {
+        *self == JobState::Downloaded
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JobInput {
+    pub path: PathBuf,
+    pub checksum: String,
+    pub url: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JobRecord {
+    pub tx_hash: String,
+    pub task_name: String,
+    pub chunk_id: usize,
+    pub prover_hash: String,
+    pub verifier_hash: String,
+    pub trace: JobInput,
+    pub bi: JobInput,
+    pub asm: JobInput,
+    pub http_server_work_path: PathBuf,
+    pub http_url: String,
+    #[serde(default)]
+    pub circom: CircomOverrides,
+    /// The inputs were served by the embedded file server, which must serve them again when the
+    /// job is resumed.
+    #[serde(default)]
+    pub served: bool,
+    pub state: JobState,
+    pub out_dir: Option<PathBuf>,
+    pub result_files: Vec<PathBuf>,
+    pub error: Option<String>,
+    /// Unix timestamps, in seconds.
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+impl JobRecord {
+    pub fn new(
+        tx_hash: &str,
+        request: &ProvingRequest,
+        inputs: &StagedInputs,
+        served: bool,
+    ) -> Self {
+        let input = |path: &Path, file: &StagedFile| JobInput {
+            path: path.to_path_buf(),
+            checksum: file.checksum.clone(),
+            url: file.url.to_string(),
+        };
+        let now = now();
+        JobRecord {
+            tx_hash: tx_hash.to_string(),
+            task_name: request.task_name.clone(),
+            chunk_id: request.chunk_id,
+            prover_hash: request.prover_hash.to_string(),
+            verifier_hash: request.verifier_hash.to_string(),
+            trace: input(&request.trace_file, &inputs.trace),
+            bi: input(&request.bi_file, &inputs.bi),
+            asm: input(&request.asm_file, &inputs.asm),
+            http_server_work_path: request.http_server_work_path.clone(),
+            http_url: request.http_url.clone(),
+            circom: request.circom,
+            served,
+            state: JobState::Submitted,
+            out_dir: None,
+            result_files: vec![],
+            error: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    pub fn downloaded(&mut self, out_dir: &Path, files: Vec<PathBuf>) {
+        self.state = JobState::Downloaded;
+        self.out_dir = Some(out_dir.to_path_buf());
+        self.result_files = files;
+        self.error = None;
+        self.updated_at = now();
+    }
+
+    pub fn failed(&mut self, out_dir: &Path, error: String) {
+        self.state = JobState::Failed;
+        self.out_dir = Some(out_dir.to_path_buf());
+        self.error = Some(error);
+        self.updated_at = now();
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct JobStore {
+    dir: PathBuf,
+}
+
+impl JobStore {
+    pub fn open<P: Into<PathBuf>>(dir: P) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir).with_context(|| format!("create the job store {:?}", dir))?;
+        Ok(JobStore { dir })
+    }
+
+    /// `~/.eigen-gevulot/jobs`, or `.eigen-gevulot/jobs` without a home directory.
+    pub fn default_dir() -> PathBuf {
+        std::env::var_os("HOME")
+            .map(PathBuf::from)
+            .unwrap_or_default()
+            .join(".eigen-gevulot")
+            .join("jobs")
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, tx_hash: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", tx_hash))
+    }
+
+    pub fn save(&self, record: &JobRecord) -> Result<()> {
+        let path = self.path(&record.tx_hash);
+        let tmp = self.dir.join(format!(".{}.json.tmp", record.tx_hash));
+        fs::write(&tmp, serde_json::to_string_pretty(record)?)
+            .with_context(|| format!("write {:?}", tmp))?;
+        fs::rename(&tmp, &path).with_context(|| format!("rename {:?} to {:?}", tmp, path))?;
+        Ok(())
+    }
+
+    pub fn load(&self, tx_hash: &str) -> Result<JobRecord> {
+        let path = self.path(tx_hash);
+        if !path.is_file() {
+            bail!("no job {} in {:?}", tx_hash, self.dir);
+        }
+        let content = fs::read_to_string(&path).with_context(|| format!("read {:?}", path))?;
+        serde_json::from_str(&content).with_context(|| format!("parse {:?}", path))
+    }
+
+    /// Every job, the oldest first. Unreadable files are skipped with a warning.
+    pub fn list(&self) -> Result<Vec<JobRecord>> {
+        let mut records = vec![];
+        for entry in fs::read_dir(&self.dir).with_context(|| format!("read {:?}", self.dir))? {
+            let path = entry?.path();
+            let is_record = path.extension().is_some_and(|e| e == "json")
+                && !path
+                    .file_name()
+                    .is_some_and(|n| n.to_string_lossy().starts_with('.'));
+            if !is_record {
+                continue;
+            }
+            match fs::read_to_string(&path)
+                .map_err(anyhow::Error::from)
+                .and_then(|c| Ok(serde_json::from_str::<JobRecord>(&c)?))
+            {
+                Ok(record) => records.push(record),
+                Err(e) => log::warn!("skip the job file {:?}: {}", path, e),
+            }
+        }
+        records.sort_by_key(|r| (r.created_at, r.chunk_id));
+        Ok(records)
+    }
+
+    /// The jobs whose results aren't downloaded yet.
+    pub fn unfinished(&self) -> Result<Vec<JobRecord>> {
+        Ok(self
+            .list()?
+            .into_iter()
+            .filter(|r| !r.state.is_finished())
+            .collect())
+    }
+
+    pub fn remove(&self, tx_hash: &str) -> Result<()> {
+        let path = self.path(tx_hash);
+        fs::remove_file(&path).with_context(|| format!("remove {:?}", path))
+    }
+
+    /// Removes the downloaded jobs not updated for `older_than`, and the unfinished ones too if
+    /// `all` is set. Returns the removed jobs.
+    pub fn gc(&self, older_than: Duration, all: bool) -> Result<Vec<JobRecord>> {
+        let limit = now().saturating_sub(older_than.as_secs());
+        let mut removed = vec![];
+        for record in self.list()? {
+            if record.updated_at <= limit && (all || record.state.is_finished()) {
+                self.remove(&record.tx_hash)?;
+                removed.push(record);
+            }
+        }
+        Ok(removed)
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
@@ -1 +1,20 @@
-pub mod file;
+pub mod backend;
+pub mod batch;
+pub mod chunk;
+pub mod circom;
+pub mod client;
+pub mod config;
+pub mod deploy;
+pub mod download;
+pub mod file;
+pub use ::harness;
+pub mod jobs;
+pub mod manifest;
+pub mod ops;
+pub mod payload;
+pub mod poll;
+pub mod powdr_std;
+pub mod proof;
+pub mod server;
+pub mod stage;
+pub mod status;
@@ -0,0 +1,95 @@
+//! The output manifest written by the prover next to the chunk outputs.
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::circom::CircomOptions;
+
+use std::fs;
+use std::path::Path;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum ArtifactStatus {
+    Generated,
+    Skipped { reason: String },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Artifact {
+    pub kind: String,
+    pub path: String,
+    #[serde(flatten)]
+    pub status: ArtifactStatus,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Manifest {
+    pub task_name: String,
+    pub chunk_id: usize,
+    pub circom_options: Option<CircomOptions>,
+    pub artifacts: Vec<Artifact>,
+}
+
+impl Manifest {
+    pub fn new(task_name: &str, chunk_id: usize) -> Self {
+        Manifest {
+            task_name: task_name.to_string(),
+            chunk_id,
+            circom_options: None,
+            artifacts: vec![],
+        }
+    }
+
+    pub fn generated(&mut self, kind: &str, path: &str) {
+        self.artifacts.push(Artifact {
+            kind: kind.to_string(),
+            path: path.to_string(),
+            status: ArtifactStatus::Generated,
+        });
+    }
+
+    pub fn skipped(&mut self, kind: &str, path: &str, reason: String) {
+        self.artifacts.push(Artifact {
+            kind: kind.to_string(),
+            path: path.to_string(),
+            status: ArtifactStatus::Skipped { reason },
+        });
+    }
+
+    pub fn artifact(&self, kind: &str) -> Option<&Artifact> {
+        self.artifacts.iter().find(|a| a.kind == kind)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("write manifest {:?}", path))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path).with_context(|| format!("read {:?}", path))?;
+        serde_json::from_str(&content).with_context(|| format!("decode manifest {:?}", path))
+    }
+}
+
+/// The files produced by the prover for one chunk, relative to its output path.
+#[derive(Debug, Clone)]
+pub struct ChunkFiles {
+    pub circom: String,
+    pub proof: String,
+    pub zkin: String,
+    pub manifest: String,
+}
+
+impl ChunkFiles {
+    pub fn new(output_path: &str, task_name: &str, chunk_id: usize) -> Self {
+        let stem = format!("{}/{}_chunk_{}", output_path, task_name, chunk_id);
+        ChunkFiles {
+            circom: format!("{}.circom", stem),
+            proof: format!("{}/{}_proof.bin", stem, task_name),
+            zkin: format!("{}.zkin.json", stem),
+            manifest: format!("{}.manifest.json", stem),
+        }
+    }
+}
@@ -0,0 +1,167 @@
+//! Generates the OPS image manifest (`my_prover.json`) of a program.
+//!
+//! The powdr std library is packaged as plain `.asm` files and loaded at proving time through
+//! `POWDR_STD`, so every file of the std tree must be listed in `Files`. The files are collected
+//! by walking the `mod` declarations from the root `mod.asm`, and a declared module without its
+//! file is reported before packaging.
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "PascalCase")]
+pub struct OpsManifest {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub manifest_passthrough: BTreeMap<String, String>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    pub program: String,
+    #[serde(default)]
+    pub mounts: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<String>,
+}
+
+impl OpsManifest {
+    /// The manifest shared by the prover and the verifier: a read only root file system, the
+    /// `/workspace` mount and the logging.
+    pub fn new(program: &str, rust_log: &str) -> Self {
+        OpsManifest {
+            manifest_passthrough: BTreeMap::from([(
+                "readonly_rootfs".to_string(),
+                "true".to_string(),
+            )]),
+            env: BTreeMap::from([
+                ("RUST_BACKTRACE".to_string(), "1".to_string()),
+                ("RUST_LOG".to_string(), rust_log.to_string()),
+            ]),
+            program: program.to_string(),
+            mounts: BTreeMap::from([("%1".to_string(), "/workspace".to_string())]),
+            files: vec![],
+        }
+    }
+
+    /// Adds the files of the powdr std library at `std_dir`, a path relative to the packaging
+    /// directory, and points `POWDR_STD` to it.
+    pub fn with_powdr_std(mut self, std_dir: &Path) -> Result<Self> {
+        let files = powdr_std_files(std_dir)?;
+        self.env
+            .insert("POWDR_STD".to_string(), path_string(std_dir)?);
+        for file in files {
+            self.add_file(&file)?;
+        }
+        Ok(self)
+    }
+
+    pub fn add_file(&mut self, file: &Path) -> Result<()> {
+        if !file.is_file() {
+            bail!("the file {:?} doesn't exist", file);
+        }
+        let file = path_string(file)?;
+        if !self.files.contains(&file) {
+            self.files.push(file);
+        }
+        Ok(())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("write {:?}", path))
+    }
+}
+
+/// The `.asm` files of the powdr std library, starting from `<std_dir>/mod.asm`.
+///
+/// A module `m` declared by `mod m;` is `m.asm` or `m/mod.asm`, next to a `mod.asm` and in the
+/// directory of the same name otherwise. Every declared module must exist, and every `.asm` file
+/// of the tree must be declared.
+pub fn powdr_std_files(std_dir: &Path) -> Result<Vec<PathBuf>> {
+    let root = std_dir.join("mod.asm");
+    if !root.is_file() {
+        bail!("{:?} isn't a powdr std directory, no mod.asm", std_dir);
+    }
+    let mut declared = BTreeSet::new();
+    let mut missing = vec![];
+    let mut pending = vec![root];
+    while let Some(file) = pending.pop() {
+        let content = fs::read_to_string(&file).with_context(|| format!("read {:?}", file))?;
+        let dir = module_dir(&file);
+        for name in module_declarations(&content) {
+            let flat = dir.join(format!("{}.asm", name));
+            let nested = dir.join(&name).join("mod.asm");
+            if flat.is_file() {
+                pending.push(flat);
+            } else if nested.is_file() {
+                pending.push(nested);
+            } else {
+                missing.push(format!("{} (declared in {:?})", name, file));
+            }
+        }
+        declared.insert(file);
+    }
+    if !missing.is_empty() {
+        bail!("missing powdr std modules: {}", missing.join(", "));
+    }
+
+    let mut undeclared = vec![];
+    for file in asm_files(std_dir)? {
+        if !declared.contains(&file) {
+            undeclared.push(format!("{:?}", file));
+        }
+    }
+    if !undeclared.is_empty() {
+        bail!(
+            "the powdr std files {} aren't declared by any mod.asm",
+            undeclared.join(", ")
+        );
+    }
+    Ok(declared.into_iter().collect())
+}
+
+// The directory of the submodules of a module file.
+fn module_dir(file: &Path) -> PathBuf {
+    let parent = file.parent().unwrap_or(Path::new(""));
+    match (file.file_name(), file.file_stem()) {
+        (Some(name), _) if name == "mod.asm" => parent.to_path_buf(),
+        (_, Some(stem)) => parent.join(stem),
+        _ => parent.to_path_buf(),
+    }
+}
+
+// The `mod name;` declarations of a file, the inline `mod name { .. }` are skipped.
+fn module_declarations(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(|line| line.split("//").next().unwrap_or_default().trim())
+        .filter_map(|line| {
+            let line = line.strip_prefix("pub ").unwrap_or(line).trim_start();
+            let name = line.strip_prefix("mod ")?.strip_suffix(';')?.trim();
+            (!name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_'))
+                .then(|| name.to_string())
+        })
+        .collect()
+}
+
+fn asm_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    for entry in fs::read_dir(dir).with_context(|| format!("read {:?}", dir))? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(asm_files(&path)?);
+        } else if path.extension().is_some_and(|e| e == "asm") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn path_string(path: &Path) -> Result<String> {
+    match path.to_str() {
+        Some(path) => Ok(path.to_string()),
+        None => bail!("the path {:?} isn't valid UTF-8", path),
+    }
+}
@@ -0,0 +1,213 @@
+//! Typed decoding of the transactions returned by `RpcClient::get_transaction`.
+//!
+//! The types mirror the JSON form of gevulot_node's `TransactionOutput`, hashes are hex
+//! strings and binary data (proof, verification) is base64 encoded.
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use std::fmt;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TxOutput {
+    pub author: String,
+    pub hash: String,
+    pub payload: TxPayload,
+    pub nonce: u64,
+    pub signature: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum TxPayload {
+    Empty,
+    Transfer {
+        to: String,
+        value: Value,
+    },
+    Stake {
+        value: Value,
+    },
+    Unstake {
+        value: Value,
+    },
+    Deploy {
+        name: String,
+        prover: Value,
+        verifier: Value,
+    },
+    Run {
+        workflow: Workflow,
+    },
+    Proof(ProofPayload),
+    ProofKey {
+        parent: String,
+        key: String,
+    },
+    Verification(VerificationPayload),
+    Cancel {
+        parent: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Workflow {
+    pub steps: Vec<WorkflowStep>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct WorkflowStep {
+    pub program: String,
+    pub args: Vec<String>,
+    pub inputs: Vec<ProgramData>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum ProgramData {
+    Input {
+        file_name: String,
+        file_url: String,
+        checksum: String,
+    },
+    Output {
+        source_program: String,
+        file_name: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ProofPayload {
+    pub parent: String,
+    pub prover: String,
+    pub proof: String,
+    pub files: Vec<TxFile>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct VerificationPayload {
+    pub parent: String,
+    pub verifier: String,
+    pub verification: String,
+    pub files: Vec<TxFile>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TxFile {
+    pub url: String,
+    pub checksum: String,
+    pub vm_path: String,
+}
+
+#[derive(Debug)]
+pub enum PayloadError {
+    Decode {
+        hash: String,
+        error: serde_json::Error,
+    },
+    UnexpectedVariant {
+        hash: String,
+        expected: &'static str,
+        found: &'static str,
+    },
+}
+
+impl fmt::Display for PayloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PayloadError::Decode { hash, error } => {
+                write!(f, "decode the transaction {} error: {}", hash, error)
+            }
+            PayloadError::UnexpectedVariant {
+                hash,
+                expected,
+                found,
+            } => write!(
+                f,
+                "the transaction {} has a {} payload, expected {}",
+                hash, found, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PayloadError {}
+
+impl TxPayload {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TxPayload::Empty => "Empty",
+            TxPayload::Transfer { .. } => "Transfer",
+            TxPayload::Stake { .. } => "Stake",
+            TxPayload::Unstake { .. } => "Unstake",
+            TxPayload::Deploy { .. } => "Deploy",
+            TxPayload::Run { .. } => "Run",
+            TxPayload::Proof(_) => "Proof",
+            TxPayload::ProofKey { .. } => "ProofKey",
+            TxPayload::Verification(_) => "Verification",
+            TxPayload::Cancel { .. } => "Cancel",
+        }
+    }
+}
+
+impl TxOutput {
+    /// Decodes the output of `RpcClient::get_transaction`.
+    pub fn decode<T: Serialize>(hash: &str, tx_output: &T) -> Result<Self, PayloadError> {
+        serde_json::to_value(tx_output)
+            .and_then(serde_json::from_value)
+            .map_err(|error| PayloadError::Decode {
+                hash: hash.to_string(),
+                error,
+            })
+    }
+
+    /// Converts the JSON form of a sent `Transaction`, whose author and signature may not be
+    /// strings, to the form returned by the node.
+    pub fn from_sent(tx: &Value) -> Result<Self, PayloadError> {
+        let text = |key: &str| match &tx[key] {
+            Value::String(s) => s.clone(),
+            Value::Null => String::new(),
+            v => v.to_string(),
+        };
+        let hash = text("hash");
+        let payload = serde_json::from_value(tx["payload"].clone()).map_err(|error| {
+            PayloadError::Decode {
+                hash: hash.clone(),
+                error,
+            }
+        })?;
+        Ok(TxOutput {
+            author: text("author"),
+            hash,
+            payload,
+            nonce: tx["nonce"].as_u64().unwrap_or_default(),
+            signature: text("signature"),
+        })
+    }
+
+    pub fn into_verification(self) -> Result<VerificationPayload, PayloadError> {
+        match self.payload {
+            TxPayload::Verification(v) => Ok(v),
+            other => Err(unexpected(self.hash, "Verification", &other)),
+        }
+    }
+
+    pub fn into_proof(self) -> Result<ProofPayload, PayloadError> {
+        match self.payload {
+            TxPayload::Proof(p) => Ok(p),
+            other => Err(unexpected(self.hash, "Proof", &other)),
+        }
+    }
+
+    pub fn into_workflow(self) -> Result<Workflow, PayloadError> {
+        match self.payload {
+            TxPayload::Run { workflow } => Ok(workflow),
+            other => Err(unexpected(self.hash, "Run", &other)),
+        }
+    }
+}
+
+fn unexpected(hash: String, expected: &'static str, found: &TxPayload) -> PayloadError {
+    PayloadError::UnexpectedVariant {
+        hash,
+        expected,
+        found: found.kind(),
+    }
+}
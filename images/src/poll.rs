@@ -0,0 +1,176 @@
+//! The polling policy used while waiting for a transaction to be proved and verified.
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct PollPolicy {
+    /// Waiting time before the first poll.
+    pub initial_delay: Duration,
+    /// Waiting time after the first poll, multiplied by `multiplier` after every poll.
+    pub interval: Duration,
+    pub max_interval: Duration,
+    pub multiplier: f64,
+    /// The random part of every interval, from 0 (none) to 1 (up to the whole interval).
+    pub jitter: f64,
+    /// Overall deadline, counted from the start of the waiting.
+    pub deadline: Duration,
+}
+
+impl Default for PollPolicy {
+    fn default() -> Self {
+        PollPolicy {
+            initial_delay: Duration::from_secs(30),
+            interval: Duration::from_secs(10),
+            max_interval: Duration::from_secs(120),
+            multiplier: 1.5,
+            jitter: 0.2,
+            deadline: Duration::from_secs(3600),
+        }
+    }
+}
+
+impl PollPolicy {
+    /// The waiting time after the `attempt`-th poll (starting at 1). A non-finite multiplier is
+    /// taken as 1 and a non-finite jitter as 0.
+    pub fn interval(&self, attempt: u32) -> Duration {
+        let multiplier = if self.multiplier.is_finite() {
+            self.multiplier.max(1.0)
+        } else {
+            1.0
+        };
+        let exp = multiplier.powi(attempt.saturating_sub(1).min(i32::MAX as u32) as i32);
+        let base = Duration::try_from_secs_f64(self.interval.as_secs_f64() * exp)
+            .unwrap_or(Duration::MAX)
+            .min(self.max_interval);
+        let jitter = if self.jitter.is_finite() {
+            self.jitter.clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        if jitter == 0.0 {
+            return base;
+        }
+        // spread the interval over [base * (1 - jitter), base]
+        base.mul_f64(1.0 - jitter * random_fraction(attempt))
+    }
+}
+
+fn random_fraction(seed: u32) -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(seed);
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PollError {
+    /// The transaction tree was found, but its verification never completed before the deadline.
+    Timeout {
+        tx_hash: String,
+        elapsed: Duration,
+        attempts: u32,
+    },
+    /// The node never returned a transaction tree for the hash before the deadline.
+    NotFound {
+        tx_hash: String,
+        elapsed: Duration,
+        attempts: u32,
+        last_error: String,
+    },
+    /// A leaf of the transaction tree couldn't be fetched or decoded.
+    Leaf {
+        tx_hash: String,
+        leaf: String,
+        error: String,
+    },
+}
+
+impl fmt::Display for PollError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PollError::Timeout {
+                tx_hash,
+                elapsed,
+                attempts,
+            } => write!(
+                f,
+                "timeout: tx {} isn't verified after {:?} and {} attempts",
+                tx_hash, elapsed, attempts
+            ),
+            PollError::NotFound {
+                tx_hash,
+                elapsed,
+                attempts,
+                last_error,
+            } => write!(
+                f,
+                "not found: no tx tree for {} after {:?} and {} attempts, last error: {}",
+                tx_hash, elapsed, attempts, last_error
+            ),
+            PollError::Leaf {
+                tx_hash,
+                leaf,
+                error,
+            } => write!(f, "the leaf {} of tx {} error: {}", leaf, tx_hash, error),
+        }
+    }
+}
+
+impl std::error::Error for PollError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(multiplier: f64, jitter: f64) -> PollPolicy {
+        PollPolicy {
+            initial_delay: Duration::ZERO,
+            interval: Duration::from_secs(1),
+            max_interval: Duration::from_secs(60),
+            multiplier,
+            jitter,
+            deadline: Duration::from_secs(3600),
+        }
+    }
+
+    #[test]
+    fn interval_grows_by_the_multiplier() {
+        let policy = policy(2.0, 0.0);
+        let intervals: Vec<u64> = (1..=5).map(|a| policy.interval(a).as_secs()).collect();
+        assert_eq!(intervals, [1, 2, 4, 8, 16]);
+        // the first poll waits the interval too
+        assert_eq!(policy.interval(0), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn interval_is_capped() {
+        let policy = policy(2.0, 0.0);
+        assert_eq!(policy.interval(7), Duration::from_secs(60));
+        assert_eq!(policy.interval(u32::MAX), Duration::from_secs(60));
+        // a multiplier below 1 doesn't shrink the interval
+        assert_eq!(self::policy(0.5, 0.0).interval(3), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn jitter_stays_in_bounds() {
+        let policy = policy(1.0, 0.25);
+        for attempt in 1..200 {
+            let interval = policy.interval(attempt);
+            assert!(interval >= Duration::from_millis(750), "{:?}", interval);
+            assert!(interval <= Duration::from_secs(1), "{:?}", interval);
+        }
+        // a jitter above 1 is the whole interval at most
+        for attempt in 1..200 {
+            assert!(self::policy(1.0, 5.0).interval(attempt) <= Duration::from_secs(1));
+        }
+    }
+
+    #[test]
+    fn non_finite_options_are_ignored() {
+        for value in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            assert_eq!(policy(value, 0.0).interval(4), Duration::from_secs(1));
+            assert_eq!(policy(2.0, value).interval(4), Duration::from_secs(8));
+        }
+    }
+}
@@ -0,0 +1,85 @@
+//! The powdr std library embedded into the prover by `build.rs`.
+//!
+//! The NanoVM image doesn't need to ship the std `.asm` files: `install` writes the embedded copy
+//! to a directory and points `POWDR_STD` to it. The embedded files are checked against the std
+//! library of the powdr revision pinned in `Cargo.lock` first.
+use anyhow::{bail, Context, Result};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+include!(concat!(env!("OUT_DIR"), "/powdr_std.rs"));
+
+pub const POWDR_STD_ENV: &str = "POWDR_STD";
+// Written after the files, a directory with the same hash is reused.
+const HASH_FILE: &str = ".hash";
+
+/// The hash of a std library, from its files sorted by relative path.
+pub fn tree_hash<'a, I: IntoIterator<Item = (&'a str, &'a [u8])>>(files: I) -> String {
+    let mut entries: Vec<_> = files.into_iter().collect();
+    entries.sort();
+    let mut hasher = blake3::Hasher::new();
+    for (name, content) in entries {
+        hasher.update(name.as_bytes());
+        hasher.update(&[0]);
+        hasher.update(&(content.len() as u64).to_le_bytes());
+        hasher.update(content);
+    }
+    hasher.finalize().to_hex().to_string()
+}
+
+/// Checks that the embedded files are intact and are the std library of the pinned powdr.
+pub fn check() -> Result<()> {
+    let hash = tree_hash(POWDR_STD_FILES.iter().copied());
+    if hash != POWDR_STD_HASH {
+        bail!(
+            "the embedded powdr std library is corrupted: {} instead of {}",
+            hash,
+            POWDR_STD_HASH
+        );
+    }
+    match POWDR_STD_EXPECTED_HASH {
+        Some(expected) if expected != hash => bail!(
+            "the embedded powdr std library {} isn't the one of the powdr crate {}, update images/scripts/powdr",
+            hash,
+            expected
+        ),
+        Some(_) => log::info!("powdr std library {} ({} files)", hash, POWDR_STD_FILES.len()),
+        None => log::warn!(
+            "powdr std library {} ({} files), not checked against the powdr crate",
+            hash,
+            POWDR_STD_FILES.len()
+        ),
+    }
+    Ok(())
+}
+
+/// Writes the embedded files to `dir`, unless it already holds them.
+pub fn materialize(dir: &Path) -> Result<()> {
+    let hash_file = dir.join(HASH_FILE);
+    if fs::read_to_string(&hash_file).is_ok_and(|hash| hash == POWDR_STD_HASH) {
+        return Ok(());
+    }
+    for (name, content) in POWDR_STD_FILES {
+        let path = dir.join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).with_context(|| format!("create {:?}", parent))?;
+        }
+        fs::write(&path, content).with_context(|| format!("write {:?}", path))?;
+    }
+    fs::write(&hash_file, POWDR_STD_HASH).with_context(|| format!("write {:?}", hash_file))?;
+    Ok(())
+}
+
+/// Checks the embedded std library, writes it to `<work_dir>/powdr_std` and sets `POWDR_STD`.
+///
+/// Must be called before any other thread is started, as it sets an environment variable: the
+/// prover calls it in `main`, before `harness::run`.
+pub fn install(work_dir: &Path) -> Result<PathBuf> {
+    check()?;
+    let dir = work_dir.join("powdr_std");
+    materialize(&dir)?;
+    std::env::set_var(POWDR_STD_ENV, &dir);
+    log::info!("{}={:?}", POWDR_STD_ENV, dir);
+    Ok(dir)
+}
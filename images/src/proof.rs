@@ -0,0 +1,423 @@
+//! Decoding of the starky proof written by the prover (`<task>_proof.bin`).
+//!
+//! The estark backend serializes `starky::stark_gen::StarkProof` with serde_json, so the
+//! file is a flat JSON object laid out like the circom verifier input: `root1`..`root4`,
+//! `evals`, the FRI query data `s{i}_*`, `finalPol`, `publics` and optionally `rootC`.
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use starky::types::StarkStruct;
+
+use std::fs;
+use std::path::Path;
+
+use crate::manifest::{ArtifactStatus, ChunkFiles, Manifest};
+
+const ROOT_KEYS: [&str; 5] = ["root1", "root2", "root3", "root4", "rootC"];
+const REQUIRED_KEYS: [&str; 7] = [
+    "root1", "root2", "root3", "root4", "evals", "finalPol", "s0_vals1",
+];
+
+#[derive(Debug, Clone)]
+pub struct StarkProofFile {
+    pub path: String,
+    pub size: u64,
+    pub fields: Map<String, Value>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct NamedValue {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SectionSize {
+    pub name: String,
+    pub bytes: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct FriLayer {
+    pub step: usize,
+    pub root: Option<String>,
+    pub queries: usize,
+    pub vals_width: usize,
+    pub siblings_depth: usize,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ProofSummary {
+    pub path: String,
+    pub size: u64,
+    pub roots: Vec<NamedValue>,
+    pub publics: Vec<String>,
+    pub n_evals: usize,
+    pub final_pol_len: usize,
+    pub n_queries: usize,
+    pub fri_layers: Vec<FriLayer>,
+    pub sections: Vec<SectionSize>,
+    pub stark_struct: Option<StarkStruct>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ProofDifference {
+    pub key: String,
+    pub left: Option<String>,
+    pub right: Option<String>,
+}
+
+pub fn load_proof<P: AsRef<Path>>(path: P) -> Result<StarkProofFile> {
+    let path = path.as_ref();
+    let bytes = fs::read(path).with_context(|| format!("read proof {:?}", path))?;
+    let fields: Map<String, Value> = serde_json::from_slice(&bytes)
+        .with_context(|| format!("decode proof {:?} as a starky proof", path))?;
+    for key in REQUIRED_KEYS {
+        if !fields.contains_key(key) {
+            bail!("{:?} is not a starky proof: missing `{}`", path, key);
+        }
+    }
+    Ok(StarkProofFile {
+        path: path.to_string_lossy().to_string(),
+        size: bytes.len() as u64,
+        fields,
+    })
+}
+
+pub fn load_stark_struct<P: AsRef<Path>>(path: P) -> Result<StarkStruct> {
+    let path = path.as_ref();
+    let content = fs::read_to_string(path).with_context(|| format!("read {:?}", path))?;
+    serde_json::from_str(&content).with_context(|| format!("decode StarkStruct {:?}", path))
+}
+
+pub fn summarize(proof: &StarkProofFile, stark_struct: Option<StarkStruct>) -> ProofSummary {
+    let fields = &proof.fields;
+
+    let roots = ROOT_KEYS
+        .iter()
+        .filter_map(|k| {
+            fields.get(*k).map(|v| NamedValue {
+                name: k.to_string(),
+                value: render(v),
+            })
+        })
+        .collect();
+
+    let publics = fields
+        .get("publics")
+        .and_then(Value::as_array)
+        .map(|p| p.iter().map(render).collect())
+        .unwrap_or_default();
+
+    let mut sections: Vec<SectionSize> = fields
+        .iter()
+        .map(|(k, v)| SectionSize {
+            name: k.clone(),
+            bytes: v.to_string().len(),
+        })
+        .collect();
+    sections.sort_by(|a, b| b.bytes.cmp(&a.bytes));
+
+    let fri_layers = fri_layers(fields);
+    let n_queries = fri_layers.first().map(|l| l.queries).unwrap_or(0);
+
+    ProofSummary {
+        path: proof.path.clone(),
+        size: proof.size,
+        roots,
+        publics,
+        n_evals: array_len(fields.get("evals")),
+        final_pol_len: array_len(fields.get("finalPol")),
+        n_queries,
+        fri_layers,
+        sections,
+        stark_struct,
+    }
+}
+
+/// Compares two proofs key by key. Large values are reported by their length only.
+pub fn diff_proofs(left: &StarkProofFile, right: &StarkProofFile) -> Vec<ProofDifference> {
+    let mut keys: Vec<&String> = left.fields.keys().chain(right.fields.keys()).collect();
+    keys.sort();
+    keys.dedup();
+
+    keys.into_iter()
+        .filter_map(|key| {
+            let l = left.fields.get(key);
+            let r = right.fields.get(key);
+            if l == r {
+                return None;
+            }
+            Some(ProofDifference {
+                key: key.clone(),
+                left: l.map(short_render),
+                right: r.map(short_render),
+            })
+        })
+        .collect()
+}
+
+/// The input expected by the circom verifier generated by `pil2circom`. The serialized proof is
+/// already laid out as that input, like the zkin `stark_prove` writes, so this is an identity
+/// conversion whatever the circom options: `publics` is always a field of the proof.
+pub fn to_zkin(proof: &StarkProofFile) -> Value {
+    Value::Object(proof.fields.clone())
+}
+
+pub fn write_zkin<P: AsRef<Path>>(proof: &StarkProofFile, out: P) -> Result<()> {
+    let out = out.as_ref();
+    let zkin = serde_json::to_string(&to_zkin(proof))?;
+    fs::write(out, zkin).with_context(|| format!("write zkin {:?}", out))
+}
+
+pub fn print_summary(summary: &ProofSummary) {
+    println!("proof: {} ({} bytes)", summary.path, summary.size);
+    for root in &summary.roots {
+        println!("  {}: {}", root.name, root.value);
+    }
+    println!(
+        "  publics ({}): {:?}",
+        summary.publics.len(),
+        summary.publics
+    );
+    println!("  evals: {}", summary.n_evals);
+    println!("  finalPol: {}", summary.final_pol_len);
+    println!("  queries: {}", summary.n_queries);
+    println!("  FRI layers:");
+    for layer in &summary.fri_layers {
+        println!(
+            "    step {}: root {} vals width {} siblings depth {}",
+            layer.step,
+            layer.root.as_deref().unwrap_or("-"),
+            layer.vals_width,
+            layer.siblings_depth
+        );
+    }
+    if let Some(ss) = &summary.stark_struct {
+        let steps: Vec<usize> = ss.steps.iter().map(|s| s.nBits).collect();
+        println!(
+            "  StarkStruct: nBits {} nBitsExt {} nQueries {} hash {} steps {:?}",
+            ss.nBits, ss.nBitsExt, ss.nQueries, ss.verificationHashType, steps
+        );
+    }
+    println!("  size breakdown:");
+    for section in &summary.sections {
+        println!("    {:<16} {:>12} bytes", section.name, section.bytes);
+    }
+}
+
+fn fri_layers(fields: &Map<String, Value>) -> Vec<FriLayer> {
+    let mut layers = vec![FriLayer {
+        step: 0,
+        root: None,
+        queries: array_len(fields.get("s0_vals1")),
+        vals_width: first_len(fields.get("s0_vals1")),
+        siblings_depth: first_len(fields.get("s0_siblings1")),
+    }];
+
+    let mut step = 1;
+    while let Some(root) = fields.get(&format!("s{}_root", step)) {
+        let vals = fields.get(&format!("s{}_vals", step));
+        layers.push(FriLayer {
+            step,
+            root: Some(render(root)),
+            queries: array_len(vals),
+            vals_width: first_len(vals),
+            siblings_depth: first_len(fields.get(&format!("s{}_siblings", step))),
+        });
+        step += 1;
+    }
+    layers
+}
+
+fn array_len(v: Option<&Value>) -> usize {
+    v.and_then(Value::as_array).map(Vec::len).unwrap_or(0)
+}
+
+fn first_len(v: Option<&Value>) -> usize {
+    array_len(v.and_then(Value::as_array).and_then(|a| a.first()))
+}
+
+fn render(v: &Value) -> String {
+    match v {
+        Value::String(s) => s.clone(),
+        Value::Array(a) => format!("[{}]", a.iter().map(render).collect::<Vec<_>>().join(",")),
+        other => other.to_string(),
+    }
+}
+
+fn short_render(v: &Value) -> String {
+    match v {
+        Value::Array(a) if a.len() > 4 => format!("array of {} elements", a.len()),
+        other => render(other),
+    }
+}
+
+/// The result of checking the files downloaded for one chunk.
+#[derive(Serialize, Debug)]
+pub struct ResultsCheck {
+    pub manifest: String,
+    pub checked: Vec<String>,
+    pub problems: Vec<String>,
+}
+
+impl ResultsCheck {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Checks the results of a chunk downloaded to `dir`: every artifact generated according to the
+/// manifest is there, the proof decodes and the zkin is the one of the proof.
+pub fn check_results(dir: &Path, task_name: &str, chunk_id: usize) -> Result<ResultsCheck> {
+    let names = ChunkFiles::new("", task_name, chunk_id);
+    let local = |path: &str| dir.join(Path::new(path).file_name().unwrap_or_default());
+    let manifest_file = local(&names.manifest);
+    let manifest = Manifest::load(&manifest_file)?;
+    let mut check = ResultsCheck {
+        manifest: manifest_file.to_string_lossy().to_string(),
+        checked: vec![],
+        problems: vec![],
+    };
+    if manifest.task_name != task_name || manifest.chunk_id != chunk_id {
+        check.problems.push(format!(
+            "the manifest is the one of {} chunk {}",
+            manifest.task_name, manifest.chunk_id
+        ));
+    }
+
+    if !manifest
+        .artifact("proof")
+        .is_some_and(|a| a.status == ArtifactStatus::Generated)
+    {
+        check.problems.push("no proof in the manifest".to_string());
+    }
+
+    let mut proof = None;
+    for artifact in &manifest.artifacts {
+        if let ArtifactStatus::Skipped { reason } = &artifact.status {
+            log::info!("{} was skipped: {}", artifact.kind, reason);
+            continue;
+        }
+        let file = local(&artifact.path);
+        match fs::metadata(&file) {
+            Ok(metadata) if metadata.len() > 0 => check.checked.push(artifact.kind.clone()),
+            _ => {
+                check.problems.push(format!(
+                    "the {} file {:?} is missing or empty",
+                    artifact.kind, file
+                ));
+                continue;
+            }
+        }
+        match artifact.kind.as_str() {
+            "proof" => match load_proof(&file) {
+                Ok(p) => proof = Some(p),
+                Err(e) => check.problems.push(format!("{:#}", e)),
+            },
+            "zkin" => {
+                let zkin: Option<Value> = fs::read(&file)
+                    .ok()
+                    .and_then(|bytes| serde_json::from_slice(&bytes).ok());
+                let expected = match &proof {
+                    Some(proof) => Some(to_zkin(proof)),
+                    None => load_proof(local(&names.proof)).ok().map(|p| to_zkin(&p)),
+                };
+                if zkin.is_none() || zkin != expected {
+                    check
+                        .problems
+                        .push(format!("the zkin {:?} doesn't match the proof", file));
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(check)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn proof(extra: Value) -> StarkProofFile {
+        let mut fields = json!({
+            "root1": "1", "root2": "2", "root3": "3", "root4": "4",
+            "evals": [["1", "2"]],
+            "finalPol": [["1"]],
+            "s0_vals1": [["1", "2", "3"]],
+        });
+        fields
+            .as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        StarkProofFile {
+            path: "proof.bin".to_string(),
+            size: 0,
+            fields: fields.as_object().unwrap().clone(),
+        }
+    }
+
+    fn temp_file(name: &str, content: &[u8]) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("images-proof-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn load_a_proof() {
+        let content = serde_json::to_vec(&to_zkin(&proof(json!({ "publics": ["7"] })))).unwrap();
+        let path = temp_file("lr_proof.bin", &content);
+        let loaded = load_proof(&path).unwrap();
+        assert_eq!(loaded.size, content.len() as u64);
+        assert_eq!(loaded.fields["publics"], json!(["7"]));
+    }
+
+    #[test]
+    fn load_rejects_non_proofs() {
+        let not_json = temp_file("not_json.bin", &[0, 159, 146, 150]);
+        assert!(load_proof(&not_json).is_err());
+
+        let array = temp_file("array.bin", b"[1, 2]");
+        assert!(load_proof(&array).is_err());
+
+        // a JSON object, but without the FRI queries
+        let mut fields = proof(json!({})).fields;
+        fields.remove("s0_vals1");
+        let partial = temp_file("partial.bin", &serde_json::to_vec(&fields).unwrap());
+        let err = load_proof(&partial).unwrap_err();
+        assert!(err.to_string().contains("missing `s0_vals1`"), "{}", err);
+
+        assert!(load_proof(temp_file("empty.bin", b"")).is_err());
+        assert!(load_proof(partial.with_file_name("missing.bin")).is_err());
+    }
+
+    #[test]
+    fn diff_equal_proofs() {
+        assert!(diff_proofs(&proof(json!({})), &proof(json!({}))).is_empty());
+    }
+
+    #[test]
+    fn diff_changed_and_missing_keys() {
+        let left = proof(json!({ "rootC": "5", "publics": ["1", "2", "3", "4", "5"] }));
+        let right = proof(json!({ "root1": "9", "publics": ["1", "2", "3", "4", "6"] }));
+        let diffs = diff_proofs(&left, &right);
+        let keys: Vec<&str> = diffs.iter().map(|d| d.key.as_str()).collect();
+        assert_eq!(keys, ["publics", "root1", "rootC"]);
+
+        // a large value is reported by its length
+        assert_eq!(diffs[0].left.as_deref(), Some("array of 5 elements"));
+        assert_eq!(diffs[1].left.as_deref(), Some("1"));
+        assert_eq!(diffs[1].right.as_deref(), Some("9"));
+        assert_eq!(diffs[2].left.as_deref(), Some("5"));
+        assert_eq!(diffs[2].right, None);
+    }
+
+    #[test]
+    fn zkin_is_the_proof() {
+        let proof = proof(json!({ "publics": ["7"] }));
+        assert_eq!(to_zkin(&proof), Value::Object(proof.fields.clone()));
+    }
+}
@@ -17,9 +17,14 @@ use starky::{
     stark_setup::StarkSetup,
     types::{StarkStruct, Step},
 };
+use images::chunk::read_bootloader_inputs;
+use images::circom::{CircomOptions, CircomOverrides};
+use images::manifest::{ChunkFiles, Manifest};
+use images::proof::{load_proof, write_zkin};
 use std::fs::{self, create_dir_all /*, remove_dir_all*/};
 use std::io::BufWriter;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 use std::time::Instant;
 
 const TEST_CHANNEL: u32 = 1;
@@ -42,10 +47,11 @@ fn generate_witness_and_prove<F: FieldElement>(
     Ok(())
 }
 
-fn generate_verifier<F: FieldElement, W: std::io::Write>(
+// Returns None when the setup has no quotient polynomials and no circom can be generated.
+fn generate_verifier<F: FieldElement>(
     mut pipeline: Pipeline<F>,
-    mut writer: W,
-) -> Result<()> {
+    opt: &pil2circom::StarkOption,
+) -> Result<Option<String>> {
     let buf = Vec::new();
     let mut vw = BufWriter::new(buf);
     pipeline = pipeline.with_backend(BackendType::EStarkStarky, Some("stark_gl".to_string()));
@@ -76,26 +82,20 @@ fn generate_verifier<F: FieldElement, W: std::io::Write>(
     };
 
     // generate circom
-    let opt = pil2circom::StarkOption {
-        enable_input: false,
-        verkey_input: false,
-        skip_main: true,
-        agg_stage: false,
-    };
-    if !setup.starkinfo.qs.is_empty() {
-        let pil_json = pil_export::<F>(pil);
-        let str_ver = pil2circom::pil2circom(
-            &pil_json,
-            &setup.const_root,
-            &params,
-            &mut setup.starkinfo,
-            &mut setup.program,
-            &opt,
-        )
-        .unwrap();
-        writer.write_fmt(format_args!("{}", str_ver))?;
+    if setup.starkinfo.qs.is_empty() {
+        return Ok(None);
     }
-    Ok(())
+    let pil_json = pil_export::<F>(pil);
+    let str_ver = pil2circom::pil2circom(
+        &pil_json,
+        &setup.const_root,
+        &params,
+        &mut setup.starkinfo,
+        &mut setup.program,
+        opt,
+    )
+    .unwrap();
+    Ok(Some(str_ver))
 }
 
 pub fn zkvm_execute_and_prove(task: &str, suite_json: String, output_path: &str) -> Result<()> {
@@ -161,62 +161,20 @@ pub fn zkvm_execute_and_prove(task: &str, suite_json: String, output_path: &str)
     Ok(())
 }
 
-pub fn zkvm_generate_chunks(
-    workspace: &str,
-    suite_json: &String,
-    output_path: &str,
-) -> Result<Vec<(Vec<GoldilocksField>, u64)>> {
-    log::info!("Compiling Rust...");
-    let force_overwrite = true;
-    let with_bootloader = true;
-    let (asm_file_path, asm_contents) = compile_rust::<GoldilocksField>(
-        workspace,
-        Path::new(output_path),
-        force_overwrite,
-        &Runtime::base().with_poseidon(),
-        with_bootloader,
-    )
-    .ok_or_else(|| vec!["could not compile rust".to_string()])
-    .unwrap();
-
-    let mut pipeline = Pipeline::<GoldilocksField>::default()
-        .with_output(output_path.into(), true)
-        .from_asm_string(asm_contents.clone(), Some(asm_file_path.clone()))
-        .with_prover_inputs(Default::default())
-        .add_data(TEST_CHANNEL, suite_json);
-
-    log::info!("Running powdr-riscv executor in fast mode...");
-
-
-    log::info!("Running powdr-riscv executor in trace mode for continuations...");
-    let start = Instant::now();
-
-    let bootloader_inputs = rust_continuations_dry_run(&mut pipeline);
-
-    let duration = start.elapsed();
-    log::info!(
-        "Trace executor took: {:?}, input size: {:?}",
-        duration,
-        bootloader_inputs.len()
-    );
-
-    Ok(bootloader_inputs)
-}
-
 pub fn zkvm_prove_only(
     task: &str,
+    asm_file_path: &Path,
     suite_json: &String,
     bootloader_input: Vec<GoldilocksField>,
     start_of_shutdown_routine: u64,
     i: usize,
     output_path: &str,
+    circom_options: &CircomOptions,
 ) -> Result<()> {
     log::info!("Compiling Rust...");
-    let asm_file_path = Path::new(output_path).join(format!("{}.asm", task));
-
     let pipeline = Pipeline::<GoldilocksField>::default()
         .with_output(output_path.into(), true)
-        .from_asm_file(asm_file_path.clone())
+        .from_asm_file(asm_file_path.to_path_buf())
         .with_prover_inputs(Default::default())
         .add_data(TEST_CHANNEL, suite_json);
 
@@ -234,13 +192,41 @@ pub fn zkvm_prove_only(
     )
     .unwrap();
  
-    let verifier_file = Path::new(output_path).join(format!("{}_chunk_{}.circom", task, i));
+    let files = ChunkFiles::new(output_path, task, i);
+    let mut manifest = Manifest::new(task, i);
+    manifest.circom_options = Some(*circom_options);
+    manifest.generated("proof", &files.proof);
+
     log::info!(
         "Running circom verifier generation to {:?}...",
-        verifier_file
+        files.circom
     );
-    let f = fs::File::create(verifier_file)?;
-    generate_verifier(pipeline, f).unwrap();
+    // The verifier step always expects the circom file, so it is created even when skipped.
+    let mut f = fs::File::create(&files.circom)?;
+    match generate_verifier(pipeline, &circom_options.to_stark_option()).unwrap() {
+        Some(str_ver) => {
+            f.write_all(str_ver.as_bytes())?;
+            manifest.generated("circom", &files.circom);
+        }
+        None => {
+            log::warn!("setup.starkinfo.qs is empty, skip the circom verifier generation");
+            manifest.skipped(
+                "circom",
+                &files.circom,
+                "setup.starkinfo.qs is empty".to_string(),
+            );
+        }
+    }
+
+    log::info!("Writing circom input to {:?}...", files.zkin);
+    match load_proof(&files.proof).and_then(|proof| write_zkin(&proof, &files.zkin)) {
+        Ok(()) => manifest.generated("zkin", &files.zkin),
+        Err(e) => {
+            log::warn!("Failed to write circom input: {:?}", e);
+            manifest.skipped("zkin", &files.zkin, format!("{:#}", e));
+        }
+    }
+    manifest.save(&files.manifest)?;
 
     let duration = start.elapsed();
     log::info!(
@@ -315,6 +301,7 @@ struct Cli {
     trace_file: String,
     #[arg( long = "bi_file", default_value = "lr_chunks_0.data")]
     bi_file: String,
+    //the compiled program of the task, the prover reads it instead of compiling the program.
     #[arg( long = "asm_file", default_value = "lr.asm")]
     asm_file: String,
     #[arg( long = "task_name", default_value = "lr")]
@@ -325,91 +312,77 @@ struct Cli {
     #[arg(long = "output_path", default_value = "/workspace")] //must use the default value!!
     output_path: String,
 
+    //the StarkOption of the circom verifier, the following options override the config file.
+    #[arg(long = "circom_config")]
+    circom_config: Option<String>,
+    #[command(flatten)]
+    circom: CircomOverrides,
+
 }
 
 
 use gevulot_shim::{Task, TaskResult};
+use images::harness::{self, GeResult, Outputs, TaskContext, WORKSPACE};
+use images::powdr_std;
 
-type GeResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+// Installed before the harness starts the threads of the shim, as it sets `POWDR_STD`.
+static POWDR_STD_DIR: OnceLock<std::result::Result<PathBuf, String>> = OnceLock::new();
 
 fn main()-> GeResult<()>  {
-   gevulot_shim::run(run_task)
+   // the std library is embedded, not packaged into the image
+   let std_dir = powdr_std::install(Path::new(WORKSPACE)).map_err(|e| format!("{:#}", e));
+   let _ = POWDR_STD_DIR.set(std_dir);
+   harness::run(run_task)
 }
 
 fn run_task(task: Task) -> GeResult<TaskResult> {
+    harness::execute(&task, "prover", "/workspace/debug.log", prove)
+}
 
-    let start = Instant::now();
-    env_logger::init();
-    
-    log::info!("0xEigenLabs prover : task.args: {:?}", &task.args);
-
-    //let args =  Cli::parse();
-    let args =  Cli::parse_from(&task.args);
-
-    log::info!("parameters: trace_file:{};  bootloader input file:{}",args.trace_file, args.bi_file);
-    log::info!("parameters: task_name:{};  number_chunk:{}",args.task_name, args.chunk_id);
-
-    let mut log_file = fs::File::create("/workspace/debug.log")?;
-    write!(log_file, "trace_file:{}\n",  &args.trace_file)?;
-    write!(log_file, "bi_file:{}\n",  &args.bi_file)?;
-    write!(log_file, "task_name:{}\n",  &args.task_name)?;
-    write!(log_file, "number_chunk:{}\n",  &args.chunk_id)?;
-    write!(log_file, "output_path:{}\n",  &args.output_path)?;
-
-  
+fn prove(args: Cli, ctx: &mut TaskContext) -> Result<Outputs> {
+    let circom_options = match &args.circom_config {
+        Some(config) => CircomOptions::load(config)?,
+        None => CircomOptions::default(),
+    }
+    .with_overrides(args.circom);
+    ctx.log(format!("circom_options:{:?}", &circom_options));
+
+    let std_dir = POWDR_STD_DIR
+        .get()
+        .expect("installed by main")
+        .clone()
+        .map_err(|e| anyhow::anyhow!("install the powdr std library: {}", e))?;
+    ctx.log(format!("powdr std:{:?}", std_dir));
+
+    // the files the prover opens
+    for input in [&args.trace_file, &args.bi_file, &args.asm_file] {
+        ctx.input(input)?;
+    }
 
     //generate proof
-    let suite_json = fs::read_to_string(args.trace_file).unwrap();
-
-    //let workspace = format!("program/{}", args.task_name);
-
-    let mut f = fs::File::open(&args.bi_file).unwrap();
-    let metadata = fs::metadata(&args.bi_file).unwrap();
-    let file_size = metadata.len() as usize;
-
-    assert!(file_size % 8 == 0);
-    // read the start_of_shutdown_routine
-    let mut buffer = [0u8; 8];
-    f.read_exact(&mut buffer).unwrap();
-    let start_of_shutdown_routine: u64 = u64::from_le_bytes(buffer);
-
-    let file_size = file_size - 8;
-    let mut buffer = vec![0; file_size];
-    f.read_exact(&mut buffer).unwrap();
-    let mut bi = vec![GoldilocksField::default(); file_size / 8];
-    bi.iter_mut().zip(buffer.chunks(8)).for_each(|(out, bin)| {
-                *out = GoldilocksField::from_bytes_le(bin);
-            });
-     write!(log_file, "start_of_shutdown_routine:{}\n",  &start_of_shutdown_routine)?;
-
-    let exec_result = zkvm_prove_only(
-                &args.task_name,
-                &suite_json,
-                bi,
-                start_of_shutdown_routine,
-                args.chunk_id,
-                &args.output_path,
-            );
+    let suite_json = fs::read_to_string(&args.trace_file)?;
 
+    let (start_of_shutdown_routine, bi) = ctx.phase("read bootloader inputs", |_| read_bootloader_inputs(&args.bi_file))?;
+    ctx.log(format!("start_of_shutdown_routine:{}", start_of_shutdown_routine));
 
-    match exec_result {
-        Err(x) => {
-            log::info!("The prover has error: {}", x);
-            write!(log_file, "The prover has error: {}\n", x)?;
-        }
-        _ => write!(log_file, "The prover executes successfully.\n")?,
-    };
-    
-    let duration = start.elapsed();
-    log::info!("The prover executes successfully, duration{:?}", &duration);
-    write!(log_file, "the proving in Gevulot duration {:?}  \n",  &duration)?;
-    
+    let files = ChunkFiles::new(&args.output_path, &args.task_name, args.chunk_id);
+    for file in [&files.proof, &files.circom, &files.zkin, &files.manifest] {
+        ctx.output(file.clone());
+    }
 
-    let circom_file = format!("{}/{}_chunk_{}.circom",&args.output_path, &args.task_name, &args.chunk_id);
-    let proof_file = format!("{}/{}_chunk_{}/{}_proof.bin",&args.output_path, &args.task_name, &args.chunk_id, &args.task_name);
-     
+    ctx.phase("prove", |_| {
+        zkvm_prove_only(
+            &args.task_name,
+            Path::new(&args.asm_file),
+            &suite_json,
+            bi,
+            start_of_shutdown_routine,
+            args.chunk_id,
+            &args.output_path,
+            &circom_options,
+        )
+    })?;
 
-    //return three files for Verifier
-     task.result(vec![], vec![String::from(proof_file),String::from(circom_file),String::from("/workspace/debug.log")])
-    
-}
\ No newline at end of file
+    //return the outputs for Verifier, the log file is added by the harness
+    Ok(Outputs::new(vec![files.proof, files.circom, files.zkin, files.manifest]))
+}
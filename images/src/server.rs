@@ -0,0 +1,416 @@
+//! A small http file server serving the staged input files to the Gevulot node.
+//!
+//! It supports `GET` and `HEAD` with single byte ranges, returns the checksum of the staged files
+//! (the first directory of their path, see `stage`) as the `ETag`, and logs every request. Only
+//! the expected files are served, any other path is not found even if it exists under the root.
+//! The server stops by itself once every expected file has been fully served.
+use anyhow::{Context, Result};
+use futures_util::Stream;
+use http_body_util::{combinators::BoxBody, BodyExt, Empty, StreamBody};
+use hyper::body::{Bytes, Frame, Incoming};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{header, Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::net::TcpListener;
+use tokio::sync::{oneshot, Notify};
+use tokio::task::JoinHandle;
+use tokio_util::io::ReaderStream;
+
+use std::collections::HashSet;
+use std::convert::Infallible;
+use std::io::{self, SeekFrom};
+use std::net::SocketAddr;
+use std::path::{Component, Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context as TaskContext, Poll};
+
+type Body = BoxBody<Bytes, io::Error>;
+
+struct ServerState {
+    root: PathBuf,
+    /// The URL paths not fully served yet.
+    pending: Mutex<HashSet<String>>,
+    /// The relative paths of every expected file, kept after it's served for the retries.
+    allowed: Mutex<HashSet<PathBuf>>,
+    idle: Notify,
+}
+
+impl ServerState {
+    fn pending(&self) -> MutexGuard<'_, HashSet<String>> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn allowed(&self) -> MutexGuard<'_, HashSet<PathBuf>> {
+        self.allowed.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn served(&self, path: &str) {
+        let mut pending = self.pending();
+        if pending.remove(path) {
+            log::info!("{} fetched, {} files left", path, pending.len());
+            if pending.is_empty() {
+                // stores a permit if the server loop isn't waiting right now
+                self.idle.notify_one();
+            }
+        }
+    }
+}
+
+pub struct FileServer {
+    addr: SocketAddr,
+    state: Arc<ServerState>,
+    shutdown: Option<oneshot::Sender<()>>,
+    task: JoinHandle<()>,
+}
+
+impl FileServer {
+    /// Serves the files of `root` on `addr`.
+    pub async fn start<P: Into<PathBuf>>(root: P, addr: SocketAddr) -> Result<Self> {
+        let listener = TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("bind the file server to {}", addr))?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(ServerState {
+            root: root.into(),
+            pending: Mutex::new(HashSet::new()),
+            allowed: Mutex::new(HashSet::new()),
+            idle: Notify::new(),
+        });
+        let (shutdown, shutdown_rx) = oneshot::channel();
+        let task = tokio::spawn(serve(listener, state.clone(), shutdown_rx));
+        log::info!("File server of {:?} listening on {}", state.root, addr);
+        Ok(FileServer {
+            addr,
+            state,
+            shutdown: Some(shutdown),
+            task,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.state.root
+    }
+
+    pub fn is_running(&self) -> bool {
+        !self.task.is_finished()
+    }
+
+    /// Adds URL paths the node must fetch before the server stops. Only these paths are served.
+    pub fn expect<I: IntoIterator<Item = String>>(&self, paths: I) {
+        let mut pending = self.state.pending();
+        let mut allowed = self.state.allowed();
+        for path in paths {
+            match decode_path(&path) {
+                Some(relative) => {
+                    allowed.insert(relative);
+                    pending.insert(path);
+                }
+                None => log::warn!("{} can't be served, it isn't a relative file path", path),
+            }
+        }
+    }
+
+    /// Stops accepting connections, the running transfers are completed.
+    pub async fn shutdown(mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        let _ = (&mut self.task).await;
+    }
+}
+
+impl Drop for FileServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+async fn serve(
+    listener: TcpListener,
+    state: Arc<ServerState>,
+    mut shutdown: oneshot::Receiver<()>,
+) {
+    loop {
+        let idle = state.idle.notified();
+        tokio::select! {
+            _ = &mut shutdown => break,
+            _ = idle => {
+                if state.pending().is_empty() {
+                    log::info!("All the expected files are fetched, stop the file server");
+                    break;
+                }
+            }
+            accepted = listener.accept() => {
+                let (stream, remote) = match accepted {
+                    Ok(accepted) => accepted,
+                    Err(e) => {
+                        log::warn!("file server accept error: {}", e);
+                        continue;
+                    }
+                };
+                let state = state.clone();
+                tokio::spawn(async move {
+                    let service = service_fn(move |req| handle(req, remote, state.clone()));
+                    if let Err(e) = http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await
+                    {
+                        log::warn!("file server connection from {} error: {}", remote, e);
+                    }
+                });
+            }
+        }
+    }
+}
+
+async fn handle(
+    req: Request<Incoming>,
+    remote: SocketAddr,
+    state: Arc<ServerState>,
+) -> std::result::Result<Response<Body>, Infallible> {
+    let path = req.uri().path().to_string();
+    let range = req
+        .headers()
+        .get(header::RANGE)
+        .and_then(|r| r.to_str().ok())
+        .map(str::to_string);
+    let response = match respond(&req, &path, range.as_deref(), &state).await {
+        Ok(response) => response,
+        Err(e) => {
+            log::warn!("serve {} error: {}", path, e);
+            status(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    };
+    log::info!(
+        "{} {} {} {:?} -> {}",
+        remote,
+        req.method(),
+        path,
+        range,
+        response.status()
+    );
+    Ok(response)
+}
+
+async fn respond(
+    req: &Request<Incoming>,
+    path: &str,
+    range: Option<&str>,
+    state: &Arc<ServerState>,
+) -> io::Result<Response<Body>> {
+    if req.method() != Method::GET && req.method() != Method::HEAD {
+        return Ok(status(StatusCode::METHOD_NOT_ALLOWED));
+    }
+    let Some(relative) = decode_path(path) else {
+        return Ok(status(StatusCode::BAD_REQUEST));
+    };
+    if !state.allowed().contains(&relative) {
+        return Ok(status(StatusCode::NOT_FOUND));
+    }
+    let file_path = state.root.join(&relative);
+    let mut file = match File::open(&file_path).await {
+        Ok(file) => file,
+        Err(_) => return Ok(status(StatusCode::NOT_FOUND)),
+    };
+    let metadata = file.metadata().await?;
+    if !metadata.is_file() {
+        return Ok(status(StatusCode::NOT_FOUND));
+    }
+    let size = metadata.len();
+
+    let mut builder = Response::builder().header(header::ACCEPT_RANGES, "bytes");
+    if let Some(checksum) = content_checksum(&relative) {
+        builder = builder.header(header::ETAG, format!("\"{}\"", checksum));
+    }
+    let (start, end) = match range {
+        None => {
+            builder = builder.status(StatusCode::OK);
+            (0, size)
+        }
+        Some(range) => match parse_range(range, size) {
+            Some((start, end)) => {
+                builder = builder.status(StatusCode::PARTIAL_CONTENT).header(
+                    header::CONTENT_RANGE,
+                    format!("bytes {}-{}/{}", start, end - 1, size),
+                );
+                (start, end)
+            }
+            None => {
+                let mut response = status(StatusCode::RANGE_NOT_SATISFIABLE);
+                response.headers_mut().insert(
+                    header::CONTENT_RANGE,
+                    format!("bytes */{}", size).parse().expect("header value"),
+                );
+                return Ok(response);
+            }
+        },
+    };
+    builder = builder.header(header::CONTENT_LENGTH, end - start);
+
+    if req.method() == Method::HEAD {
+        return Ok(builder.body(empty()).expect("response"));
+    }
+    file.seek(SeekFrom::Start(start)).await?;
+    let stream = Tracked {
+        inner: ReaderStream::new(file.take(end - start)),
+        // only a transfer reaching the end of the file completes the fetch
+        on_complete: (end == size).then(|| (state.clone(), path.to_string())),
+    };
+    Ok(builder
+        .body(StreamBody::new(stream).boxed())
+        .expect("response"))
+}
+
+// Streams the file and marks it as served once the whole body is sent.
+struct Tracked<S> {
+    inner: S,
+    on_complete: Option<(Arc<ServerState>, String)>,
+}
+
+impl<S> Stream for Tracked<S>
+where
+    S: Stream<Item = io::Result<Bytes>> + Unpin,
+{
+    type Item = io::Result<Frame<Bytes>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut TaskContext<'_>) -> Poll<Option<Self::Item>> {
+        match Pin::new(&mut self.inner).poll_next(cx) {
+            Poll::Ready(None) => {
+                if let Some((state, path)) = self.on_complete.take() {
+                    state.served(&path);
+                }
+                Poll::Ready(None)
+            }
+            Poll::Ready(Some(item)) => Poll::Ready(Some(item.map(Frame::data))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+fn status(code: StatusCode) -> Response<Body> {
+    Response::builder()
+        .status(code)
+        .body(empty())
+        .expect("response")
+}
+
+fn empty() -> Body {
+    Empty::<Bytes>::new()
+        .map_err(|never| match never {})
+        .boxed()
+}
+
+/// Parses a single `bytes=` range into `[start, end)`.
+fn parse_range(range: &str, size: u64) -> Option<(u64, u64)> {
+    let spec = range.strip_prefix("bytes=")?.trim();
+    if spec.contains(',') {
+        return None;
+    }
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            let suffix: u64 = suffix.parse().ok()?;
+            (size.saturating_sub(suffix), size)
+        }
+        (start, "") => (start.parse().ok()?, size),
+        (start, end) => {
+            let end: u64 = end.parse().ok()?;
+            (start.parse().ok()?, end.saturating_add(1).min(size))
+        }
+    };
+    (start < end && start < size).then_some((start, end))
+}
+
+/// The relative file path of a URL path, without `..` and with the `%XX` escapes decoded.
+fn decode_path(path: &str) -> Option<PathBuf> {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            // a truncated escape is malformed
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    let decoded = String::from_utf8(decoded).ok()?;
+    let relative = PathBuf::from(decoded.trim_start_matches('/'));
+    relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)))
+        .then_some(relative)
+}
+
+// The staged files are stored under their checksum, see `StagingArea::stage`.
+fn content_checksum(relative: &Path) -> Option<String> {
+    let first = relative.components().next()?.as_os_str().to_str()?;
+    (first.len() == 64 && first.chars().all(|c| c.is_ascii_hexdigit())).then(|| first.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_single_ranges() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Some((0, 100)));
+        assert_eq!(parse_range("bytes=500-", 1000), Some((500, 1000)));
+        // the end is clamped to the size
+        assert_eq!(parse_range("bytes=900-1999", 1000), Some((900, 1000)));
+        assert_eq!(parse_range("bytes=1000-", 1000), None);
+        assert_eq!(parse_range("bytes=5-4", 1000), None);
+        assert_eq!(parse_range("bytes=0-1,5-6", 1000), None);
+        assert_eq!(parse_range("items=0-1", 1000), None);
+    }
+
+    #[test]
+    fn parse_suffix_ranges() {
+        assert_eq!(parse_range("bytes=-100", 1000), Some((900, 1000)));
+        // a suffix longer than the file is the whole file
+        assert_eq!(parse_range("bytes=-5000", 1000), Some((0, 1000)));
+        assert_eq!(parse_range("bytes=-0", 1000), None);
+        assert_eq!(parse_range("bytes=-100", 0), None);
+        assert_eq!(parse_range("bytes=-", 1000), None);
+    }
+
+    #[test]
+    fn decode_escapes() {
+        assert_eq!(
+            decode_path("/abc/my%20file.bin"),
+            Some(PathBuf::from("abc/my file.bin"))
+        );
+        assert_eq!(
+            decode_path("/abc%2Fd.bin"),
+            Some(PathBuf::from("abc/d.bin"))
+        );
+        assert_eq!(decode_path("/a%zz"), None);
+    }
+
+    #[test]
+    fn decode_rejects_parent_dirs() {
+        assert_eq!(decode_path("/../etc/passwd"), None);
+        assert_eq!(decode_path("/%2e%2e/etc/passwd"), None);
+        assert_eq!(decode_path("/abc/%2E%2E%2F%2E%2E/etc"), None);
+    }
+
+    #[test]
+    fn decode_rejects_a_trailing_escape() {
+        assert_eq!(decode_path("/abc%"), None);
+        assert_eq!(decode_path("/abc%2"), None);
+        assert_eq!(decode_path("/abc%25"), Some(PathBuf::from("abc%")));
+    }
+}
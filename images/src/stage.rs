@@ -0,0 +1,173 @@
+//! Staging of the input files into the directory served by the http file server.
+//!
+//! A file is copied to `<work_path>/<checksum>/<file name>`, so different files with the same name
+//! never collide, a file staged twice is reused and editing the source doesn't change the staged
+//! copy.
+use anyhow::{anyhow, bail, Context, Result};
+use url::Url;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use crate::client::ProvingRequest;
+use crate::file::file_checksum;
+
+/// An input file in the served directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StagedFile {
+    pub source: PathBuf,
+    /// The file name of the source, used as the file name in the Gevulot VM.
+    pub file_name: String,
+    pub path: PathBuf,
+    pub checksum: String,
+    pub url: Url,
+}
+
+/// The directory served by the http file server and its base URL.
+#[derive(Debug, Clone)]
+pub struct StagingArea {
+    pub work_path: PathBuf,
+    pub base_url: Url,
+}
+
+impl StagingArea {
+    pub fn new<P: Into<PathBuf>>(work_path: P, http_url: &str) -> Result<Self> {
+        let base_url = Url::parse(http_url).with_context(|| format!("invalid url {}", http_url))?;
+        if base_url.cannot_be_a_base() || !matches!(base_url.scheme(), "http" | "https") {
+            bail!("{} must be an http or https URL", http_url);
+        }
+        Ok(StagingArea {
+            work_path: work_path.into(),
+            base_url,
+        })
+    }
+
+    /// The URL of a path relative to the work path.
+    pub fn url_of(&self, relative: &Path) -> Result<Url> {
+        let mut url = self.base_url.clone();
+        {
+            let mut segments = url
+                .path_segments_mut()
+                .map_err(|_| anyhow!("{} can't be a base url", self.base_url))?;
+            segments.pop_if_empty();
+            for component in relative.components() {
+                segments.push(&component.as_os_str().to_string_lossy());
+            }
+        }
+        Ok(url)
+    }
+
+    pub async fn stage(&self, source: &Path, hashes: &HashCache) -> Result<StagedFile> {
+        let file_name = source
+            .file_name()
+            .ok_or_else(|| anyhow!("no file name in {:?}", source))?
+            .to_string_lossy()
+            .to_string();
+        let checksum = hashes.checksum(source).await?;
+        let relative = Path::new(&checksum).join(&file_name);
+        let path = self.work_path.join(&relative);
+
+        // a copy of an earlier run, unless it was changed or partially written
+        let staged = matches!(hashes.checksum(&path).await, Ok(staged) if staged == checksum);
+        if !staged {
+            copy(source, &path)?;
+            log::info!("Staged {:?} to {:?}", source, path);
+        }
+
+        Ok(StagedFile {
+            source: source.to_path_buf(),
+            file_name,
+            url: self.url_of(&relative)?,
+            path,
+            checksum,
+        })
+    }
+}
+
+// Copies through a temporary file, so the destination is never partially written.
+fn copy(source: &Path, dest: &Path) -> Result<()> {
+    if let Some(dir) = dest.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("create {:?}", dir))?;
+    }
+    let mut tmp = dest.as_os_str().to_os_string();
+    tmp.push(".part");
+    let tmp = PathBuf::from(tmp);
+    fs::copy(source, &tmp).with_context(|| format!("copy {:?} to {:?}", source, tmp))?;
+    fs::rename(&tmp, dest).with_context(|| format!("rename {:?} to {:?}", tmp, dest))?;
+    Ok(())
+}
+
+/// The staged input files of a proving request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StagedInputs {
+    pub trace: StagedFile,
+    pub bi: StagedFile,
+    pub asm: StagedFile,
+}
+
+impl StagedInputs {
+    pub async fn stage(request: &ProvingRequest, hashes: &HashCache) -> Result<Self> {
+        let area = StagingArea::new(&request.http_server_work_path, &request.http_url)?;
+        Ok(StagedInputs {
+            trace: area.stage(&request.trace_file, hashes).await?,
+            bi: area.stage(&request.bi_file, hashes).await?,
+            asm: area.stage(&request.asm_file, hashes).await?,
+        })
+    }
+
+    pub fn files(&self) -> [&StagedFile; 3] {
+        [&self.trace, &self.bi, &self.asm]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct HashEntry {
+    len: u64,
+    modified: Option<SystemTime>,
+    checksum: String,
+}
+
+/// The checksums of the source files, computed again only when a file changes.
+#[derive(Debug, Default)]
+pub struct HashCache {
+    entries: Mutex<HashMap<PathBuf, HashEntry>>,
+}
+
+impl HashCache {
+    pub fn new() -> Self {
+        HashCache::default()
+    }
+
+    pub async fn checksum(&self, path: &Path) -> Result<String> {
+        let path = path
+            .canonicalize()
+            .with_context(|| format!("the input file {:?} doesn't exist", path))?;
+        let metadata = fs::metadata(&path)?;
+        let (len, modified) = (metadata.len(), metadata.modified().ok());
+
+        if let Some(entry) = self.lock().get(&path) {
+            if entry.len == len && entry.modified == modified {
+                return Ok(entry.checksum.clone());
+            }
+        }
+        let checksum = file_checksum(&path.to_string_lossy())
+            .await
+            .map_err(|e| anyhow!("{}", e))?;
+        self.lock().insert(
+            path,
+            HashEntry {
+                len,
+                modified,
+                checksum: checksum.clone(),
+            },
+        );
+        Ok(checksum)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<PathBuf, HashEntry>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
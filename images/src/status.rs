@@ -0,0 +1,433 @@
+//! The transaction tree of a Run transaction as data, and the state of the proving job derived
+//! from it.
+//!
+//! The node builds the tree as the workflow runs: the Run transaction is the root, the proofs
+//! are its nodes and the verifications are the leaves. `tx_status` queries the tree once and
+//! fetches the payload of every leaf, `watch_tx_status` queries it until the job is finished.
+use gevulot_node::types::{Hash, TransactionTree};
+use serde::Serialize;
+
+use std::fmt;
+use std::time::Duration;
+
+use tokio::time::{sleep, Instant};
+
+use crate::backend::ProvingBackend;
+use crate::client::parse_hash;
+use crate::file::get_tx_output;
+use crate::payload::TxPayload;
+use crate::poll::PollPolicy;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NodeKind {
+    Root,
+    Node,
+    Leaf,
+}
+
+/// A node of the transaction tree. Only the leaves carry their payload.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct TreeNode {
+    pub kind: NodeKind,
+    pub hash: String,
+    /// The payload kind of a fetched leaf, e.g. `Verification`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payload: Option<String>,
+    /// The verification of a leaf with a verification payload.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verification: Option<LeafVerification>,
+    /// Why the transaction of a leaf couldn't be fetched.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<TreeNode>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct LeafVerification {
+    pub verifier: String,
+    /// The VM paths of the result files.
+    pub files: Vec<String>,
+}
+
+impl From<&TransactionTree> for TreeNode {
+    fn from(tree: &TransactionTree) -> Self {
+        let (kind, hash, children) = match tree {
+            TransactionTree::Root { children, hash } => (NodeKind::Root, hash, children.as_slice()),
+            TransactionTree::Node { children, hash } => (NodeKind::Node, hash, children.as_slice()),
+            TransactionTree::Leaf { hash } => (NodeKind::Leaf, hash, &[][..]),
+        };
+        TreeNode {
+            kind,
+            hash: hash.to_string(),
+            payload: None,
+            verification: None,
+            error: None,
+            children: children.iter().map(TreeNode::from).collect(),
+        }
+    }
+}
+
+impl TreeNode {
+    pub fn leaves(&self) -> Vec<&TreeNode> {
+        match self.kind {
+            NodeKind::Leaf => vec![self],
+            _ => self.children.iter().flat_map(TreeNode::leaves).collect(),
+        }
+    }
+
+    fn leaves_mut(&mut self) -> Vec<&mut TreeNode> {
+        match self.kind {
+            NodeKind::Leaf => vec![self],
+            _ => self
+                .children
+                .iter_mut()
+                .flat_map(TreeNode::leaves_mut)
+                .collect(),
+        }
+    }
+
+    /// Fetches the transaction of every leaf and records its payload.
+    pub async fn fetch_leaves(&mut self, client: &impl ProvingBackend) {
+        for leaf in self.leaves_mut() {
+            let output = match parse_hash("leaf hash", Some(leaf.hash.clone())) {
+                Ok(hash) => get_tx_output(client, hash).await.map_err(|e| e.to_string()),
+                Err(e) => Err(format!("{:#}", e)),
+            };
+            match output {
+                Ok(output) => {
+                    leaf.payload = Some(output.payload.kind().to_string());
+                    if let TxPayload::Verification(v) = output.payload {
+                        leaf.verification = Some(LeafVerification {
+                            verifier: v.verifier,
+                            files: v.files.into_iter().map(|f| f.vm_path).collect(),
+                        });
+                    }
+                }
+                Err(e) => leaf.error = Some(e),
+            }
+        }
+    }
+
+    /// The tree as indented text, like `print_tx_tree`, with the payload of the leaves.
+    pub fn render(&self, indentation: usize) -> String {
+        let mut out = String::new();
+        self.write(&mut out, indentation);
+        out
+    }
+
+    fn write(&self, out: &mut String, indentation: usize) {
+        let kind = match self.kind {
+            NodeKind::Root => "Root",
+            NodeKind::Node => "Node",
+            NodeKind::Leaf => "Leaf",
+        };
+        let indent = match self.kind {
+            NodeKind::Root => String::new(),
+            _ => "\t".repeat(indentation),
+        };
+        out.push_str(&format!("{}{}: {}", indent, kind, self.hash));
+        match (&self.verification, &self.payload, &self.error) {
+            (Some(v), _, _) => out.push_str(&format!(
+                " (verified by {}, {} files)",
+                v.verifier,
+                v.files.len()
+            )),
+            (None, Some(payload), _) => out.push_str(&format!(" ({} payload)", payload)),
+            (None, None, Some(error)) => out.push_str(&format!(" (error: {})", error)),
+            (None, None, None) => {}
+        }
+        out.push('\n');
+        for child in &self.children {
+            child.write(out, indentation + 1);
+        }
+    }
+}
+
+impl fmt::Display for TreeNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.render(0))
+    }
+}
+
+/// The state of a proving job, derived from its transaction tree.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TxState {
+    /// The node has no transaction tree yet.
+    Pending,
+    /// The tree exists but no leaf is a verification of the verifier yet, e.g. the proof is a
+    /// leaf until its verification arrives.
+    Proving,
+    /// A leaf carries the verification of the verifier.
+    Verified,
+    /// The workflow was cancelled, or it wasn't verified before the deadline of the watch.
+    Failed,
+}
+
+impl TxState {
+    pub fn is_finished(&self) -> bool {
+        matches!(self, TxState::Verified | TxState::Failed)
+    }
+}
+
+impl fmt::Display for TxState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self {
+            TxState::Pending => "pending",
+            TxState::Proving => "proving",
+            TxState::Verified => "verified",
+            TxState::Failed => "failed",
+        };
+        f.write_str(state)
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct TxStatus {
+    pub tx_hash: String,
+    pub state: TxState,
+    /// The number of leaves carrying a verification of the verifier.
+    pub verified_leaves: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tree: Option<TreeNode>,
+    /// Why the tree couldn't be fetched, the job is pending until it can, or why it failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl TxStatus {
+    /// Derives the state from the tree, a leaf counts as verified if it's a verification of
+    /// `verifier_hash`, or of any verifier if none is given. Only a cancellation fails the job,
+    /// any other leaf means it's still proving.
+    pub fn new(tx_hash: &Hash, tree: TreeNode, verifier_hash: Option<&str>) -> Self {
+        let leaves = tree.leaves();
+        let verified_leaves = leaves
+            .iter()
+            .filter_map(|leaf| leaf.verification.as_ref())
+            .filter(|v| verifier_hash.map_or(true, |h| v.verifier.eq_ignore_ascii_case(h)))
+            .count();
+        let cancelled = leaves
+            .iter()
+            .any(|leaf| leaf.payload.as_deref() == Some("Cancel"));
+        let (state, error) = if verified_leaves > 0 {
+            (TxState::Verified, None)
+        } else if cancelled {
+            (
+                TxState::Failed,
+                Some("the workflow was cancelled".to_string()),
+            )
+        } else {
+            (TxState::Proving, None)
+        };
+        TxStatus {
+            tx_hash: tx_hash.to_string(),
+            state,
+            verified_leaves,
+            tree: Some(tree),
+            error,
+        }
+    }
+
+    /// The status of a job which isn't finished at the deadline.
+    pub fn timed_out(mut self, elapsed: Duration) -> Self {
+        self.error = Some(format!("{} after {:?}", self.state, elapsed));
+        self.state = TxState::Failed;
+        self
+    }
+
+    pub fn pending(tx_hash: &Hash, error: String) -> Self {
+        TxStatus {
+            tx_hash: tx_hash.to_string(),
+            state: TxState::Pending,
+            verified_leaves: 0,
+            tree: None,
+            error: Some(error),
+        }
+    }
+}
+
+impl fmt::Display for TxStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.tx_hash, self.state)?;
+        if self.verified_leaves > 0 {
+            write!(f, ", {} verified leaves", self.verified_leaves)?;
+        }
+        if let Some(error) = &self.error {
+            write!(f, " ({})", error)?;
+        }
+        match &self.tree {
+            Some(tree) => write!(f, "\n{}", tree),
+            None => writeln!(f),
+        }
+    }
+}
+
+/// Queries the transaction tree of `tx_hash` once and fetches its leaves.
+pub async fn tx_status(
+    client: &impl ProvingBackend,
+    tx_hash: &Hash,
+    verifier_hash: Option<&str>,
+) -> TxStatus {
+    match client.get_tx_tree(tx_hash).await {
+        Ok(tree) => {
+            let mut tree = TreeNode::from(&tree);
+            tree.fetch_leaves(client).await;
+            TxStatus::new(tx_hash, tree, verifier_hash)
+        }
+        Err(err) => {
+            log::debug!("get the tree of {} error: {}", tx_hash, err);
+            TxStatus::pending(tx_hash, err.to_string())
+        }
+    }
+}
+
+/// Queries the status according to the poll policy until the job is finished or the deadline
+/// is reached, and calls `on_change` with the first status and every status which differs from
+/// the previous one. Returns the last status, failed if the job isn't finished at the deadline.
+pub async fn watch_tx_status(
+    client: &impl ProvingBackend,
+    tx_hash: &Hash,
+    verifier_hash: Option<&str>,
+    policy: &PollPolicy,
+    mut on_change: impl FnMut(&TxStatus),
+) -> TxStatus {
+    let start = Instant::now();
+    sleep(policy.initial_delay).await;
+    let mut attempts = 0;
+    let mut last: Option<TxStatus> = None;
+    loop {
+        attempts += 1;
+        let status = tx_status(client, tx_hash, verifier_hash).await;
+        if last.as_ref() != Some(&status) {
+            on_change(&status);
+        }
+        if status.state.is_finished() {
+            return status;
+        }
+        let elapsed = start.elapsed();
+        if elapsed >= policy.deadline {
+            let status = status.timed_out(elapsed);
+            on_change(&status);
+            return status;
+        }
+        last = Some(status);
+        sleep(policy.interval(attempts).min(policy.deadline - elapsed)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::fixtures::*;
+    use crate::backend::MemoryBackend;
+
+    fn node(kind: NodeKind, byte: u8, payload: Option<&str>, children: Vec<TreeNode>) -> TreeNode {
+        TreeNode {
+            kind,
+            hash: hash(byte).to_string(),
+            payload: payload.map(str::to_string),
+            verification: None,
+            error: None,
+            children,
+        }
+    }
+
+    fn verified_leaf(byte: u8, verifier: u8) -> TreeNode {
+        TreeNode {
+            verification: Some(LeafVerification {
+                verifier: hash(verifier).to_string(),
+                files: vec!["/workspace/lr_proof.bin".to_string()],
+            }),
+            ..node(NodeKind::Leaf, byte, Some("Verification"), vec![])
+        }
+    }
+
+    fn root(children: Vec<TreeNode>) -> TreeNode {
+        node(NodeKind::Root, 1, None, children)
+    }
+
+    #[test]
+    fn proof_leaf_is_proving() {
+        let tree = root(vec![node(NodeKind::Leaf, 2, Some("Proof"), vec![])]);
+        let status = TxStatus::new(&hash(1), tree, Some(&hash(0xb0).to_string()));
+        assert_eq!(status.state, TxState::Proving);
+        assert_eq!(status.verified_leaves, 0);
+        assert_eq!(status.error, None);
+    }
+
+    #[test]
+    fn cancel_leaf_fails() {
+        let tree = root(vec![node(
+            NodeKind::Node,
+            2,
+            None,
+            vec![node(NodeKind::Leaf, 3, Some("Cancel"), vec![])],
+        )]);
+        let status = TxStatus::new(&hash(1), tree, None);
+        assert_eq!(status.state, TxState::Failed);
+        assert_eq!(status.error.as_deref(), Some("the workflow was cancelled"));
+    }
+
+    #[test]
+    fn only_the_verifier_counts() {
+        let tree = root(vec![verified_leaf(2, 0xb1), verified_leaf(3, 0xb1)]);
+        let status = TxStatus::new(&hash(1), tree.clone(), Some(&hash(0xb0).to_string()));
+        assert_eq!(status.state, TxState::Proving);
+        assert_eq!(status.verified_leaves, 0);
+
+        let verifier = hash(0xb1).to_string().to_uppercase();
+        let status = TxStatus::new(&hash(1), tree.clone(), Some(&verifier));
+        assert_eq!(status.state, TxState::Verified);
+        assert_eq!(status.verified_leaves, 2);
+
+        let status = TxStatus::new(&hash(1), tree, None);
+        assert_eq!(status.state, TxState::Verified);
+        assert_eq!(status.verified_leaves, 2);
+    }
+
+    #[test]
+    fn render_the_leaves() {
+        let failed = TreeNode {
+            error: Some("timeout".to_string()),
+            ..node(NodeKind::Leaf, 4, None, vec![])
+        };
+        let tree = root(vec![node(
+            NodeKind::Node,
+            2,
+            None,
+            vec![
+                node(NodeKind::Leaf, 3, Some("Proof"), vec![]),
+                verified_leaf(5, 0xb0),
+                failed,
+            ],
+        )]);
+        let expected = format!(
+            "Root: {}\n\tNode: {}\n\t\tLeaf: {} (Proof payload)\n\t\tLeaf: {} (verified by {}, 1 files)\n\t\tLeaf: {} (error: timeout)\n",
+            hash(1),
+            hash(2),
+            hash(3),
+            hash(5),
+            hash(0xb0),
+            hash(4)
+        );
+        assert_eq!(tree.render(0), expected);
+        assert_eq!(tree.to_string(), expected);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn watch_fails_at_the_deadline() {
+        let backend = MemoryBackend::new();
+        set_tree(&backend, &hash(1), &[hash(2)]);
+        insert(&backend, &hash(2), proof(&hash(1)));
+
+        let mut states = vec![];
+        let status = watch_tx_status(&backend, &hash(1), None, &policy(), |status| {
+            states.push(status.state)
+        })
+        .await;
+        assert_eq!(states, [TxState::Proving, TxState::Failed]);
+        assert!(status.error.is_some());
+    }
+}
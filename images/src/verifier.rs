@@ -2,8 +2,7 @@ extern crate clap;
 use clap::{command, Parser};
 
 use gevulot_shim::{Task, TaskResult};
-
-type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+use images::harness::{self, GeResult, Outputs, TaskContext};
 
 #[derive(Debug, Parser, Default)]
 #[command(about, version, no_binary_name(true))]
@@ -13,21 +12,23 @@ struct Cli {
     proof_file: String,
     #[arg(short, long = "circom_file", default_value = "/workspace/lr_chunk_0.circom")]
     circom_file: String,
-    
+    #[arg(short, long = "zkin_file", default_value = "/workspace/lr_chunk_0.zkin.json")]
+    zkin_file: String,
+    #[arg(short, long = "manifest_file", default_value = "/workspace/lr_chunk_0.manifest.json")]
+    manifest_file: String,
+
 }
 
-fn main()-> Result<()>  {
-   gevulot_shim::run(run_task)
+fn main()-> GeResult<()>  {
+   harness::run(run_task)
 }
 
-fn run_task(task: Task) -> Result<TaskResult> {
+fn run_task(task: Task) -> GeResult<TaskResult> {
+    harness::execute(&task, "verifier", "/workspace/verifier.log", verify)
+}
 
-    //env_logger::init();
-    println!("verifier : task.args: {:?}", &task.args);
-    let args =  Cli::parse_from(&task.args);
-      
-    //Rhe verifier does nothing. 
+fn verify(args: Cli, _ctx: &mut TaskContext) -> anyhow::Result<Outputs> {
+    //The verifier does nothing.
     //return  the files generated by the prover  to the gevulot's client.
-    task.result(vec![1,2,3], vec![String::from(&args.circom_file),String::from(&args.proof_file),String::from("/workspace/debug.log")])
-
+    Ok(Outputs::new(vec![args.circom_file, args.proof_file, args.zkin_file, args.manifest_file, String::from("/workspace/debug.log")]))
 }
@@ -1,10 +1,12 @@
 use clap::Parser;
 
-//use std::time::Duration;
+use std::time::Duration;
 use std::time::Instant;
-use std::path::PathBuf;
 
-use images::file::run_prover;
+use images::download::DownloadOptions;
+use images::client::ProvingRequest;
+use images::config::ConfigArgs;
+use images::poll::PollPolicy;
 
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
@@ -13,20 +15,10 @@ type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 #[clap(author = "Gevulot Team", version, about, long_about = None)]
 pub struct ArgConfiguration {
 
-    #[clap(short, long, default_value = "http://localhost:9944")]
-    pub json_rpc_url: String,
-    #[clap(short, long, default_value = "localkey.pki")]
-    pub key_file: PathBuf,
-    
-    /// Optional Address of the local http server use by the node to download input file.
-    #[clap(short, long, default_value = "http://4.145.88.10:8080")]
-     local_http_url: String,
-
-    #[clap(short, long, default_value = "735dd3a758ca4a7ddb17965a016068e789424eced65727549014f159e929cfa4")]
-    pub prover_hash: String,
-
-    #[clap(short, long, default_value = "2390dcb2a644823ffe63e5a0e586cf20e69ab02479b011ece3b46614189a4cff")]
-    pub verifier_hash: String,
+    //the profile of eigen-gevulot.toml: the node, the key, the http file server, the program hashes, the
+    //timeouts and the output directory.
+    #[command(flatten)]
+    config: ConfigArgs,
 
     #[arg( long = "trace_file", default_value = "solidityExample.json")]
     trace_file: String,
@@ -36,19 +28,23 @@ pub struct ArgConfiguration {
     asm_file: String,
     #[arg( long = "task_name", default_value = "lr")]
     task_name: String,
-    #[arg(long = "chunk_id", default_value = "0") ]
-    chunk_id: String,
+    #[arg(long = "chunk_id", default_value_t = 0) ]
+    chunk_id: usize,
     
-    //the http_server_work_path is set during the installation of the http file server.
-    #[arg( long = "http_server_work_path", default_value = "/data/http/")]
-    http_server_work_path: String,
-
-    //the saving path when downloading the proof file from the Gevulot server.
-    #[arg( long = "proof_file_out_path", default_value = "/tmp/gevulot/")]
-    proof_file_out_path: String,
-    #[clap(long = "rpctimeout", value_name = "RPC TIMEOUT")]
-    rpc_timeout: Option<u64>,
- 
+    //the polling of the transaction tree while the proving task is running, in seconds.
+    #[arg(long = "poll_initial_delay", default_value_t = 30)]
+    poll_initial_delay: u64,
+    #[arg(long = "poll_interval", default_value_t = 10)]
+    poll_interval: u64,
+    #[arg(long = "poll_max_interval", default_value_t = 120)]
+    poll_max_interval: u64,
+
+    //the download of the result files.
+    #[arg(long = "download_attempts", default_value_t = 5)]
+    download_attempts: u32,
+    #[arg(long = "download_concurrency", default_value_t = 4)]
+    download_concurrency: usize,
+
 }
 
 #[tokio::main]
@@ -56,22 +52,53 @@ async fn main() -> Result<()> {
     env_logger::init();
     log::info!("ZKVM-Gevulot e2e-test ...");
     let cfg = ArgConfiguration::parse();
- 
+    let config = cfg.config.load()?;
+    log::info!("config:\n{}", config.to_toml()?);
+
+    let poll_policy = PollPolicy {
+        initial_delay: Duration::from_secs(cfg.poll_initial_delay),
+        interval: Duration::from_secs(cfg.poll_interval),
+        max_interval: Duration::from_secs(cfg.poll_max_interval),
+        deadline: Duration::from_secs(config.poll_deadline),
+        ..Default::default()
+    };
+    let download_options = DownloadOptions {
+        attempts: cfg.download_attempts,
+        chunk_timeout: Duration::from_secs(config.download_timeout),
+        concurrency: cfg.download_concurrency,
+        ..Default::default()
+    };
+
+    let programs = config.programs(&cfg.task_name);
+    let mut builder = ProvingRequest::builder();
+    if let Some(hash) = programs.prover_hash {
+        builder = builder.prover_hash(hash);
+    }
+    if let Some(hash) = programs.verifier_hash {
+        builder = builder.verifier_hash(hash);
+    }
+    let request = builder
+        .trace_file(&cfg.trace_file)
+        .bi_file(&cfg.bi_file)
+        .asm_file(&cfg.asm_file)
+        .task_name(&cfg.task_name)
+        .chunk_id(cfg.chunk_id)
+        .http_server_work_path(&config.http_server_work_path)
+        .http_url(&config.http_url)
+        .build()?;
+    let client = config.client()?
+        .with_poll_policy(poll_policy)
+        .with_download_options(download_options);
+
     log::info!("before proving :");
     let start = Instant::now();
-    
 
-     run_prover(&cfg.json_rpc_url, &cfg.key_file, &cfg.prover_hash, &cfg.verifier_hash , &cfg.trace_file, &cfg.bi_file,
-                    &cfg.asm_file,
-                    &cfg.task_name,
-                    &cfg.chunk_id,
-                    &cfg.http_server_work_path,
-                    &cfg.local_http_url,
-                    &cfg.proof_file_out_path,
-                    cfg.rpc_timeout
-            ).await?; 
-
-   
+    let job = client.submit(request).await?;
+    let files = job.fetch_results(&config.out_dir).await?;
+    for file in files {
+        log::info!("Downloaded {:?}", file);
+    }
+
     let duration = start.elapsed();
     log::info!("Finish Gevulot proving, duration: {:?}", duration);
 
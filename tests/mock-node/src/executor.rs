@@ -0,0 +1,216 @@
+use anyhow::{anyhow, bail, Context, Result};
+use base64::Engine;
+use images::download::{DownloadManager, DownloadOptions, DownloadRequest};
+use images::file::file_checksum;
+use images::harness::{LocalResult, LocalTask, RESULT_FILE_ENV, TASK_FILE_ENV, WORKSPACE};
+use images::payload::{
+    ProgramData, ProofPayload, TxFile, TxOutput, TxPayload, VerificationPayload, Workflow,
+    WorkflowStep,
+};
+use images::stage::StagingArea;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+
+use crate::node::Node;
+
+/// Runs the workflows of the Run transactions with local program binaries, one at a time, as
+/// they all share the `/workspace` directory.
+pub struct Executor {
+    pub node: Arc<Node>,
+    /// The binary of every deployed program hash.
+    pub programs: HashMap<String, PathBuf>,
+    /// The result files are published to `<data_dir>/txfiles`, served at `files`.
+    pub data_dir: PathBuf,
+    pub files: StagingArea,
+    pub lock: Mutex<()>,
+}
+
+impl Executor {
+    pub fn spawn(self: &Arc<Self>, run_hash: String, workflow: Workflow) {
+        let executor = self.clone();
+        std::thread::spawn(move || {
+            let _guard = executor.lock.lock().unwrap_or_else(|e| e.into_inner());
+            let runtime = match tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+            {
+                Ok(runtime) => runtime,
+                Err(e) => return log::error!("build the runtime error: {}", e),
+            };
+            match runtime.block_on(executor.execute(&run_hash, &workflow)) {
+                Ok(()) => log::info!("The workflow of {} is finished", run_hash),
+                Err(e) => log::error!("The workflow of {} failed: {:#}", run_hash, e),
+            }
+        });
+    }
+
+    async fn execute(&self, run_hash: &str, workflow: &Workflow) -> Result<()> {
+        let mut parent = run_hash.to_string();
+        for (i, step) in workflow.steps.iter().enumerate() {
+            let hash = blake3::hash(format!("{}:{}", run_hash, i).as_bytes())
+                .to_hex()
+                .to_string();
+            log::info!("Run the step {} of {} as {}", i, run_hash, hash);
+
+            self.fetch_inputs(step).await?;
+            let result = self.run_program(&hash, step)?;
+            let files = self.publish(&hash, &result).await?;
+            let data = base64::engine::general_purpose::STANDARD
+                .encode(serde_json::to_vec(&result.report)?);
+
+            let failed = result.report.error.clone();
+            // a failed step is never a verification, its report and files are kept as a proof
+            let payload = if failed.is_none() && i > 0 && i + 1 == workflow.steps.len() {
+                TxPayload::Verification(VerificationPayload {
+                    parent: parent.clone(),
+                    verifier: step.program.clone(),
+                    verification: data,
+                    files,
+                })
+            } else {
+                TxPayload::Proof(ProofPayload {
+                    parent: parent.clone(),
+                    prover: step.program.clone(),
+                    proof: data,
+                    files,
+                })
+            };
+            let tx = TxOutput {
+                author: "mock-node".to_string(),
+                hash: hash.clone(),
+                payload,
+                nonce: 0,
+                signature: String::new(),
+            };
+            self.node.insert(tx, Some(&parent));
+
+            if let Some(error) = failed {
+                // the remaining steps aren't run, the Cancel leaf fails the job
+                let cancel = TxOutput {
+                    author: "mock-node".to_string(),
+                    hash: blake3::hash(format!("{}:{}:cancel", run_hash, i).as_bytes())
+                        .to_hex()
+                        .to_string(),
+                    payload: TxPayload::Cancel {
+                        parent: hash.clone(),
+                    },
+                    nonce: 0,
+                    signature: String::new(),
+                };
+                self.node.insert(cancel, Some(&hash));
+                bail!("the step {} ({}) failed: {}", i, step.program, error);
+            }
+            parent = hash;
+        }
+        Ok(())
+    }
+
+    async fn fetch_inputs(&self, step: &WorkflowStep) -> Result<()> {
+        let manager = DownloadManager::new(DownloadOptions::default())?;
+        for input in &step.inputs {
+            match input {
+                ProgramData::Input {
+                    file_name,
+                    file_url,
+                    checksum,
+                } => {
+                    manager
+                        .download(&DownloadRequest {
+                            url: file_url.clone(),
+                            dest: vm_path(file_name)?,
+                            checksum: Some(checksum.clone()),
+                        })
+                        .await?;
+                }
+                ProgramData::Output {
+                    source_program,
+                    file_name,
+                } => {
+                    if !vm_path(file_name)?.is_file() {
+                        bail!(
+                            "the output {} of the program {} doesn't exist",
+                            file_name,
+                            source_program
+                        );
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn run_program(&self, id: &str, step: &WorkflowStep) -> Result<LocalResult> {
+        let binary = self
+            .programs
+            .get(&step.program.to_lowercase())
+            .ok_or_else(|| anyhow!("the program {} isn't deployed", step.program))?;
+        let tasks = self.data_dir.join("tasks");
+        fs::create_dir_all(&tasks)?;
+        let task_file = tasks.join(format!("{}.json", id));
+        let result_file = tasks.join(format!("{}.result.json", id));
+        let task = LocalTask {
+            id: id.to_string(),
+            args: step.args.clone(),
+            files: step
+                .inputs
+                .iter()
+                .map(|input| match input {
+                    ProgramData::Input { file_name, .. } => file_name.clone(),
+                    ProgramData::Output { file_name, .. } => file_name.clone(),
+                })
+                .collect(),
+        };
+        fs::write(&task_file, serde_json::to_string_pretty(&task)?)?;
+        let _ = fs::remove_file(&result_file);
+
+        log::info!("Run {:?} {:?}", binary, step.args);
+        let status = Command::new(binary)
+            .env(TASK_FILE_ENV, &task_file)
+            .env(RESULT_FILE_ENV, &result_file)
+            .status()
+            .with_context(|| format!("run {:?}", binary))?;
+        let content = fs::read_to_string(&result_file)
+            .with_context(|| format!("{:?} exited with {} and no result", binary, status))?;
+        let result: LocalResult = serde_json::from_str(&content)?;
+        if let Some(error) = &result.report.error {
+            log::warn!("The program {} failed: {}", step.program, error);
+        }
+        Ok(result)
+    }
+
+    /// Copies the result files to `txfiles/<tx>/<checksum>/<name>`, like the node does.
+    async fn publish(&self, hash: &str, result: &LocalResult) -> Result<Vec<TxFile>> {
+        let mut files = vec![];
+        for file in &result.files {
+            let path = Path::new(file);
+            let name = path
+                .file_name()
+                .ok_or_else(|| anyhow!("no file name in {}", file))?;
+            let checksum = file_checksum(file).await.map_err(|e| anyhow!("{}", e))?;
+            let relative = Path::new("txfiles").join(hash).join(&checksum).join(name);
+            let dest = self.data_dir.join(&relative);
+            if let Some(dir) = dest.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            fs::copy(path, &dest).with_context(|| format!("copy {} to {:?}", file, dest))?;
+            files.push(TxFile {
+                url: self.files.url_of(&relative)?.to_string(),
+                checksum,
+                vm_path: file.clone(),
+            });
+        }
+        Ok(files)
+    }
+}
+
+// The programs run with the real /workspace directory, as in the Gevulot VM.
+fn vm_path(file_name: &str) -> Result<PathBuf> {
+    if !Path::new(file_name).starts_with(WORKSPACE) {
+        bail!("the file {} isn't in {}", file_name, WORKSPACE);
+    }
+    Ok(PathBuf::from(file_name))
+}
@@ -0,0 +1,176 @@
+//! A local stand-in for the Gevulot node.
+//!
+//! It answers the JSON-RPC methods used by the client (`sendTransaction`, `getTransaction` and
+//! `getTransactionTree`), runs the workflow of every Run transaction with the local
+//! `gevulot-prover` / `gevulot-verifier` binaries and serves their result files over http.
+use anyhow::{Context, Result};
+use clap::Parser;
+use http_body_util::{BodyExt, Full};
+use hyper::body::{Bytes, Incoming};
+use hyper::server::conn::http1;
+use hyper::service::service_fn;
+use hyper::{header, Method, Request, Response, StatusCode};
+use hyper_util::rt::TokioIo;
+use images::payload::{TxOutput, TxPayload};
+use images::server::FileServer;
+use images::stage::StagingArea;
+use serde_json::{json, Value};
+use tokio::net::TcpListener;
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+mod executor;
+mod node;
+
+use executor::Executor;
+use node::Node;
+
+#[derive(Parser, Debug)]
+#[clap(author = "Eigen Team", version, about, long_about = None)]
+struct Cli {
+    #[arg(long = "rpc_addr", default_value = "127.0.0.1:9944")]
+    rpc_addr: SocketAddr,
+    //the result files are served on files_addr, at the public url files_url.
+    #[arg(long = "files_addr", default_value = "127.0.0.1:9995")]
+    files_addr: SocketAddr,
+    #[arg(long = "files_url", default_value = "http://127.0.0.1:9995")]
+    files_url: String,
+    #[arg(long = "data_dir", default_value = "/tmp/gevulot-mock-node")]
+    data_dir: PathBuf,
+    //the deployed programs, <program hash>=<binary>, e.g. --program <Prover hash>=target/release/gevulot-prover
+    #[arg(long = "program", value_parser = parse_program, required = true)]
+    programs: Vec<(String, PathBuf)>,
+}
+
+fn parse_program(s: &str) -> std::result::Result<(String, PathBuf), String> {
+    let (hash, binary) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected <program hash>=<binary>, got {}", s))?;
+    Ok((hash.trim().to_lowercase(), PathBuf::from(binary)))
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    env_logger::init();
+    let cli = Cli::parse();
+
+    std::fs::create_dir_all(&cli.data_dir)?;
+    let data_dir = cli.data_dir.canonicalize()?;
+    let file_server = FileServer::start(&data_dir, cli.files_addr).await?;
+
+    let node = Arc::new(Node::default());
+    let executor = Arc::new(Executor {
+        node: node.clone(),
+        programs: cli.programs.into_iter().collect::<HashMap<_, _>>(),
+        files: StagingArea::new(&data_dir, &cli.files_url)?,
+        data_dir,
+        lock: Mutex::new(()),
+    });
+
+    let listener = TcpListener::bind(cli.rpc_addr)
+        .await
+        .with_context(|| format!("bind {}", cli.rpc_addr))?;
+    log::info!(
+        "Mock node listening on {}, serving the result files on {}",
+        cli.rpc_addr,
+        file_server.local_addr()
+    );
+    loop {
+        let (stream, remote) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = tokio::signal::ctrl_c() => break,
+        };
+        let node = node.clone();
+        let executor = executor.clone();
+        tokio::spawn(async move {
+            let service = service_fn(move |req| handle(req, node.clone(), executor.clone()));
+            if let Err(e) = http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                log::warn!("connection from {} error: {}", remote, e);
+            }
+        });
+    }
+    file_server.shutdown().await;
+    Ok(())
+}
+
+async fn handle(
+    req: Request<Incoming>,
+    node: Arc<Node>,
+    executor: Arc<Executor>,
+) -> std::result::Result<Response<Full<Bytes>>, Infallible> {
+    if req.method() != Method::POST {
+        return Ok(Response::builder()
+            .status(StatusCode::METHOD_NOT_ALLOWED)
+            .body(Full::new(Bytes::new()))
+            .expect("response"));
+    }
+    let response = match req.into_body().collect().await {
+        Ok(body) => match serde_json::from_slice::<Value>(&body.to_bytes()) {
+            Ok(call) => call_method(&call, &node, &executor),
+            Err(e) => rpc_error(Value::Null, -32700, &format!("parse error: {}", e)),
+        },
+        Err(e) => rpc_error(Value::Null, -32700, &format!("read error: {}", e)),
+    };
+    Ok(Response::builder()
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Full::new(Bytes::from(response.to_string())))
+        .expect("response"))
+}
+
+fn call_method(call: &Value, node: &Node, executor: &Arc<Executor>) -> Value {
+    let id = call["id"].clone();
+    let method = call["method"].as_str().unwrap_or_default();
+    let param = &call["params"][0];
+    log::info!("{} {}", method, param);
+
+    let result = match method {
+        "sendTransaction" => match send_transaction(param, node, executor) {
+            Ok(()) => json!({ "Ok": null }),
+            Err(e) => {
+                log::warn!("sendTransaction error: {:#}", e);
+                json!({ "Err": { "InvalidRequest": format!("{:#}", e) } })
+            }
+        },
+        "getTransaction" => {
+            let hash = param.as_str().unwrap_or_default();
+            match node.get(hash) {
+                Some(tx) => json!({ "Ok": tx }),
+                None => json!({ "Err": { "NotFound": hash } }),
+            }
+        }
+        "getTransactionTree" => {
+            let hash = param.as_str().unwrap_or_default();
+            match node.tree(hash) {
+                Some(tree) => json!({ "Ok": tree }),
+                None => json!({ "Err": { "NotFound": hash } }),
+            }
+        }
+        _ => return rpc_error(id, -32601, &format!("method {} not found", method)),
+    };
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+fn rpc_error(id: Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+fn send_transaction(tx: &Value, node: &Node, executor: &Arc<Executor>) -> Result<()> {
+    let tx = TxOutput::from_sent(tx)?;
+    let workflow = match &tx.payload {
+        TxPayload::Run { workflow } => Some(workflow.clone()),
+        _ => None,
+    };
+    let hash = tx.hash.clone();
+    node.insert(tx, None);
+    if let Some(workflow) = workflow {
+        executor.spawn(hash, workflow);
+    }
+    Ok(())
+}
@@ -0,0 +1,61 @@
+use images::payload::TxOutput;
+use serde_json::{json, Value};
+
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+/// The transactions of the mock node and their children.
+#[derive(Default)]
+pub struct Node {
+    state: Mutex<NodeState>,
+}
+
+#[derive(Default)]
+struct NodeState {
+    txs: HashMap<String, TxOutput>,
+    children: HashMap<String, Vec<String>>,
+}
+
+impl Node {
+    fn lock(&self) -> MutexGuard<'_, NodeState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn insert(&self, tx: TxOutput, parent: Option<&str>) {
+        let mut state = self.lock();
+        if let Some(parent) = parent {
+            state
+                .children
+                .entry(parent.to_string())
+                .or_default()
+                .push(tx.hash.clone());
+        }
+        state.txs.insert(tx.hash.clone(), tx);
+    }
+
+    pub fn get(&self, hash: &str) -> Option<TxOutput> {
+        self.lock().txs.get(hash).cloned()
+    }
+
+    /// The `TransactionTree` of a transaction, in its JSON form.
+    pub fn tree(&self, hash: &str) -> Option<Value> {
+        let state = self.lock();
+        state.txs.get(hash)?;
+        Some(subtree(&state, hash, true))
+    }
+}
+
+fn subtree(state: &NodeState, hash: &str, root: bool) -> Value {
+    let children: Vec<Value> = state
+        .children
+        .get(hash)
+        .map(|c| c.iter().map(|h| subtree(state, h, false)).collect())
+        .unwrap_or_default();
+    if root {
+        json!({ "Root": { "children": children, "hash": hash } })
+    } else if children.is_empty() {
+        json!({ "Leaf": { "hash": hash } })
+    } else {
+        json!({ "Node": { "children": children, "hash": hash } })
+    }
+}
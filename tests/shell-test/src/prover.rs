@@ -1,10 +1,15 @@
 extern crate clap;
 use clap::{command, Parser};
 
+use dsl_compile::circom_compiler;
+use recursion::{
+    compressor12_exec::exec as compressor12_exec, compressor12_setup::setup as compressor12_setup,
+};
 use starky::prove::stark_prove;
 
-use std::fs::File;
-use std::io::Write;
+use anyhow::{anyhow, Result};
+use std::fs::create_dir_all;
+use std::path::Path;
 
 #[derive(Debug, Parser, Default)]
 #[command(about, version, no_binary_name(true))]
@@ -13,12 +18,26 @@ struct Cli {
     stark_struct: String,
     #[arg(short, long = "piljson", default_value = "pil.json")]
     piljson: String,
-    #[arg(short, long = "norm_stage", action= clap::ArgAction::SetTrue)]
+    //The bool options accept `--norm_stage`, `--norm_stage ""` and `--norm_stage=true|false`,
+    //as the gevulot "cmd_args" always passes a value after the name. The single stage is a norm
+    //stage unless `--norm_stage false` is given.
+    #[arg(short, long = "norm_stage", num_args = 0..=1, default_value = "true", default_missing_value = "true", value_parser = parse_flag)]
     norm_stage: bool,
-    #[arg(long = "skip_main", action= clap::ArgAction::SetTrue)]
+    #[arg(long = "skip_main", num_args = 0..=1, default_value = "false", default_missing_value = "true", value_parser = parse_flag)]
     skip_main: bool,
-    #[arg(short, long = "agg_stage", action= clap::ArgAction::SetTrue)]
+    #[arg(short, long = "agg_stage", num_args = 0..=1, default_value = "false", default_missing_value = "true", value_parser = parse_flag)]
     agg_stage: bool,
+    //The stages to run in order, e.g. "norm,agg,final". Each stage proves the circom verifier of
+    //the previous one. Without it, a single stage is run with the above bool options.
+    #[arg(long = "stages", value_delimiter = ',')]
+    stages: Vec<String>,
+    //The StarkStruct of each stage, the last one is reused for the remaining stages.
+    #[arg(long = "stage_stark_structs", value_delimiter = ',')]
+    stage_stark_structs: Vec<String>,
+    //The include directories used to compile the circom verifier of the previous stage, required
+    //with more than one stage.
+    #[arg(long = "circom_lib", value_delimiter = ',')]
+    circom_lib: Vec<String>,
     #[arg(long = "const_pols", default_value = "pols.const")]
     const_pols: String,
     #[arg(long = "cm_pols", default_value = "pols.cm")]
@@ -34,95 +53,303 @@ struct Cli {
     prover_addr: String,
 }
 
+fn parse_flag(s: &str) -> std::result::Result<bool, String> {
+    match s {
+        "" | "true" | "1" => Ok(true),
+        "false" | "0" => Ok(false),
+        _ => Err(format!("invalid bool value `{}`", s)),
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Stage {
+    name: String,
+    norm_stage: bool,
+    agg_stage: bool,
+    skip_main: bool,
+}
+
+impl Stage {
+    fn parse(name: &str, skip_main: bool) -> Result<Stage> {
+        let (norm_stage, agg_stage, skip_main) = match name {
+            "norm" => (true, false, skip_main),
+            "agg" => (false, true, skip_main),
+            //the final stage verifier is the main component
+            "final" => (false, false, false),
+            _ => {
+                return Err(anyhow!(
+                    "unknown stage `{}`, expected norm, agg or final",
+                    name
+                ))
+            }
+        };
+        Ok(Stage {
+            name: name.to_string(),
+            norm_stage,
+            agg_stage,
+            skip_main,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct StageInputs {
+    stark_struct: String,
+    piljson: String,
+    const_pols: String,
+    cm_pols: String,
+}
+
+#[derive(Debug, Clone)]
+struct StageOutputs {
+    circom_file: String,
+    zkin: String,
+}
+
+// The last stage writes to the requested paths, the earlier ones prefix the file names with the stage.
+fn stage_file(path: &str, stage: &str, last: bool) -> String {
+    if last {
+        return path.to_string();
+    }
+    let path = Path::new(path);
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}_{}", stage, file_name))
+        .to_string_lossy()
+        .to_string()
+}
+
+// Checks the include directories before the first stage, instead of failing to compile the
+// verifier of the first stage after proving it.
+fn check_circom_lib(circom_lib: &[String], stages: &[Stage]) -> Result<()> {
+    if stages.len() < 2 {
+        return Ok(());
+    }
+    if circom_lib.is_empty() {
+        return Err(anyhow!(
+            "--circom_lib is required to compile the verifiers of {} stages",
+            stages.len()
+        ));
+    }
+    match circom_lib.iter().find(|dir| !Path::new(dir).is_dir()) {
+        Some(dir) => Err(anyhow!("the --circom_lib directory {} isn't found", dir)),
+        None => Ok(()),
+    }
+}
+
+// Compiles the circom verifier of the previous stage and builds its pil, constant and committed polynomials.
+fn recursive_inputs(
+    stage: &Stage,
+    prev: &StageOutputs,
+    stark_struct: &str,
+    circom_lib: &[String],
+) -> Result<StageInputs> {
+    let circom_path = Path::new(&prev.circom_file);
+    let build_dir = circom_path.with_file_name(format!("{}_build", stage.name));
+    create_dir_all(&build_dir)?;
+    let build_dir = build_dir.to_string_lossy().to_string();
+    let circuit = circom_path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string();
+
+    circom_compiler(
+        prev.circom_file.clone(),
+        "goldilocks".to_string(),
+        "full".to_string(),
+        circom_lib.to_vec(),
+        build_dir.clone(),
+        false,
+        false,
+    )
+    .map_err(|e| {
+        anyhow!(
+            "stage {}: compile {} error: {:?}",
+            stage.name,
+            prev.circom_file,
+            e
+        )
+    })?;
+
+    let r1cs_file = format!("{}/{}.r1cs", build_dir, circuit);
+    let wasm_file = format!("{}/{}_js/{}.wasm", build_dir, circuit, circuit);
+    let inputs = StageInputs {
+        stark_struct: stark_struct.to_string(),
+        piljson: format!("{}/{}.pil.json", build_dir, circuit),
+        const_pols: format!("{}/{}.const", build_dir, circuit),
+        cm_pols: format!("{}/{}.cm", build_dir, circuit),
+    };
+    let exec_file = format!("{}/{}.exec", build_dir, circuit);
+
+    compressor12_setup(
+        &r1cs_file,
+        &inputs.piljson,
+        &inputs.const_pols,
+        &exec_file,
+        0,
+    )
+    .map_err(|e| anyhow!("stage {}: compressor12 setup error: {:?}", stage.name, e))?;
+    compressor12_exec(
+        &inputs.piljson,
+        &exec_file,
+        &wasm_file,
+        &prev.zkin,
+        &inputs.cm_pols,
+    )
+    .map_err(|e| anyhow!("stage {}: compressor12 exec error: {:?}", stage.name, e))?;
+    Ok(inputs)
+}
+
+fn run_stages(args: &Cli, stages: &[Stage], ctx: &mut TaskContext) -> Result<Vec<StageOutputs>> {
+    let mut outputs: Vec<StageOutputs> = vec![];
+    for (i, stage) in stages.iter().enumerate() {
+        let last = i + 1 == stages.len();
+        let stark_struct = args
+            .stage_stark_structs
+            .get(i)
+            .or(args.stage_stark_structs.last())
+            .unwrap_or(&args.stark_struct);
+
+        let inputs = match outputs.last() {
+            None => StageInputs {
+                stark_struct: stark_struct.clone(),
+                piljson: args.piljson.clone(),
+                const_pols: args.const_pols.clone(),
+                cm_pols: args.cm_pols.clone(),
+            },
+            Some(prev) => recursive_inputs(stage, prev, stark_struct, &args.circom_lib)?,
+        };
+        let stage_outputs = StageOutputs {
+            circom_file: stage_file(&args.circom_file, &stage.name, last),
+            zkin: stage_file(&args.zkin, &stage.name, last),
+        };
+
+        ctx.log(format!(
+            "stage {}: {:?} {:?} {:?}",
+            stage.name, stage, inputs, stage_outputs
+        ));
+
+        ctx.phase(&stage.name, |_| {
+            stark_prove(
+                &inputs.stark_struct,
+                &inputs.piljson,
+                stage.norm_stage,
+                stage.skip_main,
+                stage.agg_stage,
+                &inputs.const_pols,
+                &inputs.cm_pols,
+                &stage_outputs.circom_file,
+                &stage_outputs.zkin,
+                &args.prover_addr,
+            )
+            .map_err(|e| anyhow!("stage {}: {}", stage.name, e))
+        })?;
+        ctx.output(stage_outputs.zkin.clone());
+        ctx.output(stage_outputs.circom_file.clone());
+        outputs.push(stage_outputs);
+    }
+    Ok(outputs)
+}
+
 //use gevulot_common::WORKSPACE_PATH;
 use gevulot_shim::{Task, TaskResult};
+use harness::{self, GeResult, Outputs, TaskContext};
 
-type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+fn main() -> GeResult<()> {
+    harness::run(run_task)
+}
 
-fn main() -> Result<()> {
-    gevulot_shim::run(run_task)
+fn run_task(task: Task) -> GeResult<TaskResult> {
+    harness::execute(&task, "prover", "/workspace/test.log", prove)
 }
 
-fn run_task(task: Task) -> Result<TaskResult> {
-    env_logger::init();
-
-    println!("0xEigenLabs prover : task.args: {:?}", &task.args);
-
-    let args = Cli::parse_from(&task.args);
-
-    log::info!(
-        "parameters: proof file:{}; circom fiel:{}",
-        args.zkin,
-        args.circom_file
-    );
-    log::info!(
-        "parameters: args.stark_struct:{} ; args.piljson:{}; args.const_pols:{}",
-        args.stark_struct,
-        args.piljson,
-        args.const_pols
-    );
-    log::info!(
-        "parameters: norm_stage:{} ; args.skip_main:{} ; args.agg_stage:{}",
-        args.norm_stage,
-        args.skip_main,
-        args.agg_stage
-    );
-
-    let mut log_file = File::create("/workspace/test.log")?;
-    write!(log_file, "stark_struct:{}\n", &args.stark_struct)?;
-    write!(log_file, "piljson:{}\n", &args.piljson)?;
-    write!(log_file, "const_pols:{}\n", &args.const_pols)?;
-    write!(log_file, "cm_pols:{}\n", &args.cm_pols)?;
-    write!(log_file, "proof:{}\n", &args.zkin)?;
-    write!(log_file, "circom:{}\n", &args.circom_file)?;
-
-    let exec_result = stark_prove(
-        &args.stark_struct,
-        &args.piljson,
-        //args.norm_stage,  //The current gevulot release version doesn't support bool in its "cmd_args",eg. {"name":"--norm_stage","value": ""}. The engineer gives a patch for the bug :
-        //crates/cli/src/lib.rs  +138
-        /*let step = WorkflowStep {
-            program: (&(hex::decode(args.program)
-                .map_err(|err| format!("program decoding hash error:{err}"))?)[..])
-                .into(),
-            args: args
-                .cmd_args
-                .into_iter()
-                .flat_map(<[String; 2]>::from)
-                .filter(|x| !x.is_empty())  //fix the bug which does not support bool in the "cmd_args" .
-                .collect(),
-            inputs: input_data,
-        }; */
-        true,
-        args.skip_main,
-        args.agg_stage,
-        &args.const_pols,
-        &args.cm_pols,
-        &args.circom_file,
-        &args.zkin,
-        &args.prover_addr,
-    );
-
-    match exec_result {
-        Err(x) => {
-            log::info!("The prover has error: {}", x);
-            write!(log_file, "The prover has error: {}\n", x)?;
-        }
-        _ => write!(log_file, "The prover executes successfully.\n")?,
+fn prove(args: Cli, ctx: &mut TaskContext) -> Result<Outputs> {
+    for input in [&args.piljson, &args.const_pols, &args.cm_pols] {
+        ctx.input(input)?;
+    }
+
+    let stages = if args.stages.is_empty() {
+        vec![Stage {
+            name: "stark".to_string(),
+            norm_stage: args.norm_stage,
+            agg_stage: args.agg_stage,
+            skip_main: args.skip_main,
+        }]
+    } else {
+        args.stages
+            .iter()
+            .map(|s| Stage::parse(s, args.skip_main))
+            .collect::<Result<Vec<_>>>()?
     };
+    check_circom_lib(&args.circom_lib, &stages)?;
 
-    log::info!("The prover executes successfully");
+    let outputs = run_stages(&args, &stages, ctx)?;
 
-    // Write generated proof to a file.
-    //std::fs::write("/workspace/proof.json", b"this is a proof a.")?;
+    //return the outputs of every stage for Verifier, the log file is added by the harness
+    let files = outputs
+        .into_iter()
+        .flat_map(|o| [o.zkin, o.circom_file])
+        .collect();
+    Ok(Outputs::new(files))
+}
 
-    //return three files for Verifier
-    task.result(
-        vec![],
-        vec![
-            String::from("/workspace/proof.json"),
-            String::from("/workspace/stark_verfier.circom"),
-            String::from("/workspace/test.log"),
-        ],
-    )
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_flags() {
+        assert_eq!(parse_flag(""), Ok(true));
+        assert_eq!(parse_flag("true"), Ok(true));
+        assert_eq!(parse_flag("1"), Ok(true));
+        assert_eq!(parse_flag("false"), Ok(false));
+        assert_eq!(parse_flag("0"), Ok(false));
+        assert!(parse_flag("yes").is_err());
+    }
+
+    #[test]
+    fn bool_options() {
+        let args = Cli::try_parse_from(Vec::<&str>::new()).unwrap();
+        assert!(args.norm_stage);
+        assert!(!args.skip_main);
+        assert!(!args.agg_stage);
+
+        let args = Cli::try_parse_from(["--norm_stage", "false", "--agg_stage", ""]).unwrap();
+        assert!(!args.norm_stage);
+        assert!(args.agg_stage);
+
+        let args = Cli::try_parse_from(["--skip_main", "--norm_stage=0"]).unwrap();
+        assert!(args.skip_main);
+        assert!(!args.norm_stage);
+        assert!(Cli::try_parse_from(["--skip_main", "no"]).is_err());
+    }
+
+    #[test]
+    fn parse_stages() {
+        let norm = Stage::parse("norm", true).unwrap();
+        assert!(norm.norm_stage && !norm.agg_stage && norm.skip_main);
+        let agg = Stage::parse("agg", false).unwrap();
+        assert!(!agg.norm_stage && agg.agg_stage && !agg.skip_main);
+        // the final verifier keeps its main component
+        let last = Stage::parse("final", true).unwrap();
+        assert!(!last.norm_stage && !last.agg_stage && !last.skip_main);
+        assert!(Stage::parse("Norm", false).is_err());
+        assert!(Stage::parse("", false).is_err());
+    }
+
+    #[test]
+    fn circom_lib_of_several_stages() {
+        let stages = ["norm", "agg"]
+            .iter()
+            .map(|s| Stage::parse(s, false))
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert!(check_circom_lib(&[], &stages[..1]).is_ok());
+        assert!(check_circom_lib(&[], &stages).is_err());
+
+        let dir = std::env::temp_dir().to_string_lossy().to_string();
+        assert!(check_circom_lib(&[dir.clone()], &stages).is_ok());
+        let missing = format!("{}/no-such-circom-lib", dir);
+        assert!(check_circom_lib(&[dir, missing], &stages).is_err());
+    }
 }
@@ -1,26 +1,45 @@
 extern crate clap;
+use clap::{command, Parser};
 
 use gevulot_shim::{Task, TaskResult};
+use harness::{self, GeResult, Outputs, TaskContext};
 
-type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+#[derive(Debug, Parser, Default)]
+#[command(about, version, no_binary_name(true))]
+struct Cli {
+    #[arg(
+        long = "proof_file",
+        alias = "in_file",
+        default_value = "/workspace/proof.json"
+    )]
+    proof_file: String,
+    #[arg(
+        long = "circom_file",
+        default_value = "/workspace/stark_verfier.circom"
+    )]
+    circom_file: String,
+    #[arg(long = "log_file", default_value = "/workspace/test.log")]
+    log_file: String,
+    //the outputs of the earlier stages when the prover runs several stages
+    #[arg(long = "stage_files", value_delimiter = ',')]
+    stage_files: Vec<String>,
+}
 
-fn main() -> Result<()> {
-    gevulot_shim::run(run_task)
+fn main() -> GeResult<()> {
+    harness::run(run_task)
 }
 
-fn run_task(task: Task) -> Result<TaskResult> {
-    //env_logger::init();
-    println!("verifier : task.args: {:?}", &task.args);
+fn run_task(task: Task) -> GeResult<TaskResult> {
+    harness::execute(&task, "verifier", "/workspace/verifier.log", verify)
+}
 
+fn verify(args: Cli, _ctx: &mut TaskContext) -> anyhow::Result<Outputs> {
     //In this test, the verifier does nothing.
 
     //return  the files generated by the prover  to the gevulot's client.
-    task.result(
-        vec![1, 2, 3, 4, 5, 6, 7, 8, 9],
-        vec![
-            String::from("/workspace/proof.json"),
-            String::from("/workspace/stark_verfier.circom"),
-            String::from("/workspace/test.log"),
-        ],
-    )
+    let mut files = args.stage_files;
+    files.push(args.proof_file);
+    files.push(args.circom_file);
+    files.push(args.log_file);
+    Ok(Outputs::new(files))
 }